@@ -1,22 +1,59 @@
 mod node;
 mod async_comm_node;
+mod bloom;
+mod bracha;
+mod config;
+mod failure_detector;
+mod interval_set;
+mod merkle;
+mod rng;
+#[cfg(test)]
+mod sim;
+mod vclock;
 mod routes {
     pub mod read;
     pub mod topology;
     pub mod broadcast;
+    pub mod plumtree;
+    pub mod hyparview;
+    pub mod merkle;
+    pub mod bloom;
+    pub mod push_pull;
+    pub mod causal;
+    pub mod ordered;
+    pub mod bracha;
+    pub mod sync;
+    pub mod heartbeat;
     pub mod echo;
     pub mod init;
 }
 
 use crate::node::{CommId, MsgId, MsgType, MsgTypeType, Node, NodeId};
-use crate::async_comm_node::{AsyncCommNode, MsgCached, MsgCachedKey};
+use crate::async_comm_node::{AsyncCommNode, MsgCached, MsgCachedKey, PeerWindow};
+use crate::config::{AntiEntropyMode, BroadcastMode, ByzantineMode, Config, MembershipMode};
+use crate::failure_detector::{PeerEvent, PhiAccrualDetector};
+use crate::interval_set::IntervalSet;
 use crate::routes::read::MlstRead;
 use crate::routes::topology::MlstTopology;
+use crate::routes::broadcast::proto::MlstBodyReqBroadcast;
 use crate::routes::broadcast::MlstBroadcast;
+use crate::routes::plumtree::{MlstPlumtree, PlumtreeState};
+use crate::routes::hyparview::{HyParViewState, MlstHyParView};
+use crate::routes::merkle::{MerkleSyncState, MlstMerkleSync};
+use crate::routes::bloom::{BloomSyncState, MlstBloomSync};
+use crate::routes::heartbeat::MlstFailureDetector;
+use crate::routes::push_pull::{MlstPushPull, PushPullState};
+use crate::routes::causal::{CausalState, MlstCausalBroadcast};
+use crate::routes::ordered::{MlstOrderedBroadcast, OrderedState};
+use crate::routes::bracha::{BrachaState, MlstBracha};
+use crate::routes::sync::{HealState, MlstNeighborSync};
 use crate::routes::echo::MlstEcho;
 use crate::routes::init::MlstInit;
-use std::collections::{HashMap, HashSet};
+#[cfg(test)]
+use crate::sim::SimNode;
+use std::collections::HashMap;
 use std::io;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
 
 #[tokio::main]
@@ -26,7 +63,7 @@ async fn main() -> io::Result<()> {
         let s = Arc::clone(&service);
         async move {
             loop {
-                s.repeat_unacked();
+                s.tick();
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
             }
         }
@@ -42,23 +79,102 @@ async fn main() -> io::Result<()> {
     Ok(())
 }
 
+const PHI_THRESHOLD: f64 = 8.0;
+
 struct MlstService {
+    pub config: Config,
+    #[cfg(test)]
+    pub outbox: Mutex<Vec<String>>,
     pub node_id: Mutex<Option<NodeId>>,
+    pub node_ids: Mutex<Vec<NodeId>>,
     pub neighbor_ids: Mutex<Vec<NodeId>>,
-    pub messages: Mutex<HashSet<MsgType>>,
+    pub messages: Mutex<IntervalSet>,
     pub next_msg_id: Mutex<MsgId>,
     pub pending_ack_ids: Mutex<HashMap<MsgCachedKey, MsgCached>>,
+    pub peer_windows: Mutex<HashMap<NodeId, PeerWindow>>,
+    pub plumtree: Mutex<PlumtreeState>,
+    pub hyparview: Mutex<HyParViewState>,
+    pub merkle_sync: Mutex<MerkleSyncState>,
+    pub bloom_sync: Mutex<BloomSyncState>,
+    pub failure_detector: Mutex<PhiAccrualDetector>,
+    pub peer_events: Mutex<Receiver<PeerEvent>>,
+    pub push_pull: Mutex<PushPullState>,
+    pub causal: Mutex<CausalState>,
+    pub ordered: Mutex<OrderedState>,
+    pub bracha: Mutex<BrachaState>,
+    pub heal: Mutex<HealState>,
 }
 
 impl MlstService {
     pub fn new() -> Self {
+        Self::with_config(Config::from_env())
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        let mut failure_detector = PhiAccrualDetector::new(PHI_THRESHOLD);
+        let peer_events = failure_detector.subscribe();
         Self {
+            config,
+            #[cfg(test)]
+            outbox: Mutex::new(Vec::new()),
             node_id: Mutex::new(None),
+            node_ids: Mutex::new(Vec::new()),
             neighbor_ids: Mutex::new(Vec::new()),
-            messages: Mutex::new(HashSet::new()),
+            messages: Mutex::new(IntervalSet::new()),
             next_msg_id: Mutex::new(1),
             pending_ack_ids: Mutex::new(HashMap::new()),
+            peer_windows: Mutex::new(HashMap::new()),
+            plumtree: Mutex::new(PlumtreeState::default()),
+            hyparview: Mutex::new(HyParViewState::default()),
+            merkle_sync: Mutex::new(MerkleSyncState::default()),
+            bloom_sync: Mutex::new(BloomSyncState::default()),
+            failure_detector: Mutex::new(failure_detector),
+            peer_events: Mutex::new(peer_events),
+            push_pull: Mutex::new(PushPullState::default()),
+            causal: Mutex::new(CausalState::default()),
+            ordered: Mutex::new(OrderedState::default()),
+            bracha: Mutex::new(BrachaState::default()),
+            heal: Mutex::new(HealState::default()),
+        }
+    }
+
+    // The periodic work: failure detection, retransmissions and the gossip rounds.
+    fn tick(&self) {
+        if self.config.failure_detector {
+            self.failure_detector_tick();
+            let events: Vec<PeerEvent> = self.peer_events.lock().unwrap().try_iter().collect();
+            for event in events {
+                self.on_peer_event(&event);
+            }
         }
+        self.repeat_unacked();
+        self.plumtree_tick();
+        match self.config.broadcast {
+            BroadcastMode::PushPull => {
+                self.push_pull_tick(self.config.gossip_fanout, self.config.gossip_interval)
+            }
+            BroadcastMode::Ordered => self.ordered_tick(),
+            _ => {}
+        }
+        if self.config.membership == MembershipMode::HyParView {
+            self.hyparview_tick();
+        }
+        match self.config.anti_entropy {
+            AntiEntropyMode::Off => {}
+            AntiEntropyMode::Merkle => self.merkle_sync_tick(),
+            AntiEntropyMode::Bloom => self.bloom_sync_tick(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SimNode for MlstService {
+    fn take_outbox(&self) -> Vec<String> {
+        std::mem::take(&mut self.outbox.lock().unwrap())
+    }
+
+    fn tick(&self) {
+        MlstService::tick(self);
     }
 }
 
@@ -81,6 +197,13 @@ impl MlstTopology for MlstService {
     fn get_route_topology() -> MsgTypeType {
         return "topology".to_string();
     }
+
+    fn apply_topology(&self, neighbor_ids: Vec<NodeId>) {
+        match self.config.membership {
+            MembershipMode::Topology => self.replace_neighbors(neighbor_ids),
+            MembershipMode::HyParView => self.log("neighbors come from hyparview, topology ignored"),
+        }
+    }
 }
 
 impl MlstRead for MlstService {
@@ -88,6 +211,17 @@ impl MlstRead for MlstService {
     fn get_route_read() -> MsgTypeType {
         return "read".to_string();
     }
+
+    fn read_messages(&self) -> Vec<MsgType> {
+        match self.config.broadcast {
+            BroadcastMode::Ordered => self.ordered_messages(),
+            _ => self.messages.lock().unwrap().iter().collect(),
+        }
+    }
+
+    fn read_keeps_order(&self) -> bool {
+        self.config.broadcast == BroadcastMode::Ordered
+    }
 }
 
 impl MlstBroadcast for MlstService {
@@ -100,6 +234,317 @@ impl MlstBroadcast for MlstService {
     fn get_route_broadcast_ok() -> MsgTypeType {
         return "broadcast_ok".to_string();
     }
+
+    fn spread_broadcast(&self, src: &NodeId, req_body: &MlstBodyReqBroadcast) {
+        match self.config.broadcast {
+            BroadcastMode::Flood => self.flood_broadcast(src, req_body),
+            BroadcastMode::Plumtree => self.plumtree_broadcast(req_body.message, 0, src),
+            // gossip rounds pick the message up from the store
+            BroadcastMode::PushPull => {}
+            BroadcastMode::Causal => self.causal_broadcast(req_body.message),
+            BroadcastMode::Ordered => self.ordered_submit(req_body.message),
+            BroadcastMode::Bracha => self.bracha_broadcast(req_body.message),
+        }
+    }
+
+    fn flood_awaits_ack(&self) -> bool {
+        self.config.anti_entropy != AntiEntropyMode::Bloom
+    }
+}
+
+impl MlstPlumtree for MlstService {
+    fn get_plumtree_state(&self) -> &Mutex<PlumtreeState> {
+        &self.plumtree
+    }
+
+    #[inline]
+    fn get_route_gossip() -> MsgTypeType {
+        return "gossip".to_string();
+    }
+
+    #[inline]
+    fn get_route_gossip_ok() -> MsgTypeType {
+        return "gossip_ok".to_string();
+    }
+
+    #[inline]
+    fn get_route_ihave() -> MsgTypeType {
+        return "ihave".to_string();
+    }
+
+    #[inline]
+    fn get_route_graft() -> MsgTypeType {
+        return "graft".to_string();
+    }
+
+    #[inline]
+    fn get_route_prune() -> MsgTypeType {
+        return "prune".to_string();
+    }
+}
+
+impl MlstHyParView for MlstService {
+    fn get_hyparview_state(&self) -> &Mutex<HyParViewState> {
+        &self.hyparview
+    }
+
+    fn hpv_publish(&self, state: &HyParViewState) {
+        self.replace_neighbors(state.active.to_owned());
+    }
+
+    #[inline]
+    fn get_route_hpv_join() -> MsgTypeType {
+        return "hpv_join".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_forward_join() -> MsgTypeType {
+        return "hpv_forward_join".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_connect() -> MsgTypeType {
+        return "hpv_connect".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_disconnect() -> MsgTypeType {
+        return "hpv_disconnect".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_neighbor() -> MsgTypeType {
+        return "hpv_neighbor".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_neighbor_reply() -> MsgTypeType {
+        return "hpv_neighbor_reply".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_shuffle() -> MsgTypeType {
+        return "hpv_shuffle".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_shuffle_reply() -> MsgTypeType {
+        return "hpv_shuffle_reply".to_string();
+    }
+
+    #[inline]
+    fn get_route_hpv_heartbeat() -> MsgTypeType {
+        return "hpv_heartbeat".to_string();
+    }
+}
+
+impl MlstMerkleSync for MlstService {
+    fn get_merkle_sync_state(&self) -> &Mutex<MerkleSyncState> {
+        &self.merkle_sync
+    }
+
+    #[inline]
+    fn get_route_merkle_digest() -> MsgTypeType {
+        return "merkle_digest".to_string();
+    }
+
+    #[inline]
+    fn get_route_merkle_leaves() -> MsgTypeType {
+        return "merkle_leaves".to_string();
+    }
+
+    #[inline]
+    fn get_route_merkle_values() -> MsgTypeType {
+        return "merkle_values".to_string();
+    }
+}
+
+impl MlstBloomSync for MlstService {
+    fn get_bloom_sync_state(&self) -> &Mutex<BloomSyncState> {
+        &self.bloom_sync
+    }
+
+    #[inline]
+    fn get_route_bloom_digest() -> MsgTypeType {
+        return "bloom_digest".to_string();
+    }
+
+    #[inline]
+    fn get_route_bloom_values() -> MsgTypeType {
+        return "bloom_values".to_string();
+    }
+}
+
+impl MlstFailureDetector for MlstService {
+    fn get_failure_detector(&self) -> &Mutex<PhiAccrualDetector> {
+        &self.failure_detector
+    }
+
+    // the sequencer may be anyone, not only a neighbor
+    fn monitored_peers(&self) -> Vec<NodeId> {
+        match self.config.broadcast {
+            BroadcastMode::Ordered => self.ordered_others(),
+            _ => self.get_neighbor_ids().lock().unwrap().to_owned(),
+        }
+    }
+
+    fn on_peer_event(&self, event: &PeerEvent) {
+        match event {
+            PeerEvent::Suspect(peer) => {
+                self.log(&format!("SUSPECT {}", peer));
+                if self.config.membership == MembershipMode::HyParView {
+                    self.hpv_peer_failed(peer);
+                }
+            }
+            PeerEvent::Recover(peer) => {
+                self.log(&format!("RECOVER {}", peer));
+                self.heal_with(peer);
+            }
+        }
+    }
+
+    #[inline]
+    fn get_route_heartbeat() -> MsgTypeType {
+        return "heartbeat".to_string();
+    }
+}
+
+impl MlstPushPull for MlstService {
+    fn get_push_pull_state(&self) -> &Mutex<PushPullState> {
+        &self.push_pull
+    }
+
+    fn gossip_peers(&self) -> Vec<NodeId> {
+        match self.config.membership {
+            MembershipMode::Topology => self.all_peers(),
+            MembershipMode::HyParView => {
+                let state = self.hyparview.lock().unwrap();
+                state.active.iter().chain(state.passive.iter()).cloned().collect()
+            }
+        }
+    }
+
+    #[inline]
+    fn get_route_push_pull() -> MsgTypeType {
+        return "push_pull".to_string();
+    }
+
+    #[inline]
+    fn get_route_push_pull_ok() -> MsgTypeType {
+        return "push_pull_ok".to_string();
+    }
+}
+
+impl MlstCausalBroadcast for MlstService {
+    fn get_causal_state(&self) -> &Mutex<CausalState> {
+        &self.causal
+    }
+
+    #[inline]
+    fn get_route_causal_broadcast() -> MsgTypeType {
+        return "causal_broadcast".to_string();
+    }
+
+    #[inline]
+    fn get_route_causal_broadcast_ok() -> MsgTypeType {
+        return "causal_broadcast_ok".to_string();
+    }
+}
+
+impl MlstOrderedBroadcast for MlstService {
+    fn get_ordered_state(&self) -> &Mutex<OrderedState> {
+        &self.ordered
+    }
+
+    fn sequencer_suspected(&self, peer: &NodeId) -> bool {
+        self.config.failure_detector && self.peer_suspected(peer)
+    }
+
+    #[inline]
+    fn get_route_ord_submit() -> MsgTypeType {
+        return "ord_submit".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_accept() -> MsgTypeType {
+        return "ord_accept".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_accept_ok() -> MsgTypeType {
+        return "ord_accept_ok".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_commit() -> MsgTypeType {
+        return "ord_commit".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_nack() -> MsgTypeType {
+        return "ord_nack".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_sync() -> MsgTypeType {
+        return "ord_sync".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_takeover() -> MsgTypeType {
+        return "ord_takeover".to_string();
+    }
+
+    #[inline]
+    fn get_route_ord_takeover_ok() -> MsgTypeType {
+        return "ord_takeover_ok".to_string();
+    }
+}
+
+impl MlstBracha for MlstService {
+    fn get_bracha_state(&self) -> &Mutex<BrachaState> {
+        &self.bracha
+    }
+
+    fn byzantine_mode(&self) -> ByzantineMode {
+        self.config.byzantine
+    }
+
+    #[inline]
+    fn get_route_bracha_send() -> MsgTypeType {
+        return "bracha_send".to_string();
+    }
+
+    #[inline]
+    fn get_route_bracha_echo() -> MsgTypeType {
+        return "bracha_echo".to_string();
+    }
+
+    #[inline]
+    fn get_route_bracha_ready() -> MsgTypeType {
+        return "bracha_ready".to_string();
+    }
+
+    #[inline]
+    fn get_route_bracha_ok() -> MsgTypeType {
+        return "bracha_ok".to_string();
+    }
+}
+
+impl MlstNeighborSync for MlstService {
+    fn get_heal_state(&self) -> &Mutex<HealState> {
+        &self.heal
+    }
+
+    #[inline]
+    fn get_route_sync() -> MsgTypeType {
+        return "sync".to_string();
+    }
+
+    #[inline]
+    fn get_route_sync_ok() -> MsgTypeType {
+        return "sync_ok".to_string();
+    }
 }
 
 impl AsyncCommNode for MlstService {
@@ -114,9 +559,51 @@ impl AsyncCommNode for MlstService {
             .insert(key, msg_cached);
     }
 
+    fn skip_resend_to(&self, dest: &NodeId) -> bool {
+        self.config.failure_detector && self.peer_suspected(dest)
+    }
+
+    fn get_peer_windows(&self) -> &Mutex<HashMap<NodeId, PeerWindow>> {
+        &self.peer_windows
+    }
+
     fn ack_delivered(&self, key: &MsgCachedKey) {
         self.log(&format!("Delivered OK: {}", &key.msg_id));
-        self.pending_ack_ids.lock().unwrap().remove(key);
+        let msg_cached = self.pending_ack_ids.lock().unwrap().remove(key);
+        if let Some(msg_cached) = msg_cached {
+            self.window_acked(key, &msg_cached);
+            self.note_ack(&key.dest, &msg_cached);
+        }
+    }
+
+    fn store_message(&self, message: MsgType) {
+        self.messages.lock().unwrap().insert(message);
+        match self.config.anti_entropy {
+            AntiEntropyMode::Off => {}
+            AntiEntropyMode::Merkle => self.merkle_sync.lock().unwrap().tree.insert(message),
+            AntiEntropyMode::Bloom => {
+                self.bloom_sync.lock().unwrap().record(&IntervalSet::from_iter([message]))
+            }
+        }
+    }
+
+    fn store_messages(&self, messages: &IntervalSet) {
+        self.messages.lock().unwrap().extend(messages);
+        match self.config.anti_entropy {
+            AntiEntropyMode::Off => {}
+            AntiEntropyMode::Merkle => {
+                self.merkle_sync.lock().unwrap().tree.extend(messages.iter())
+            }
+            AntiEntropyMode::Bloom => self.bloom_sync.lock().unwrap().record(messages),
+        }
+    }
+
+    fn check_message(&self, message: &MsgType) -> bool {
+        self.messages.lock().unwrap().contains(message)
+    }
+
+    fn get_messages(&self) -> &Mutex<IntervalSet> {
+        &self.messages
     }
 }
 
@@ -137,10 +624,68 @@ impl Node for MlstService {
             "broadcast" => self.process_broadcast(comm_id, src, dest, body_req),
             "broadcast_ok" => self.process_broadcast_ok(comm_id, src, dest, body_req),
             "read" => self.process_read(comm_id, src, dest, body_req),
+            "gossip" => self.process_gossip(comm_id, src, dest, body_req),
+            "gossip_ok" => self.process_gossip_ok(comm_id, src, dest, body_req),
+            "ihave" => self.process_ihave(comm_id, src, dest, body_req),
+            "graft" => self.process_graft(comm_id, src, dest, body_req),
+            "prune" => self.process_prune(comm_id, src, dest, body_req),
+            "hpv_join" => self.process_hpv_join(comm_id, src, dest, body_req),
+            "hpv_forward_join" => self.process_hpv_forward_join(comm_id, src, dest, body_req),
+            "hpv_connect" => self.process_hpv_connect(comm_id, src, dest, body_req),
+            "hpv_disconnect" => self.process_hpv_disconnect(comm_id, src, dest, body_req),
+            "hpv_neighbor" => self.process_hpv_neighbor(comm_id, src, dest, body_req),
+            "hpv_neighbor_reply" => self.process_hpv_neighbor_reply(comm_id, src, dest, body_req),
+            "hpv_shuffle" => self.process_hpv_shuffle(comm_id, src, dest, body_req),
+            "hpv_shuffle_reply" => self.process_hpv_shuffle_reply(comm_id, src, dest, body_req),
+            "hpv_heartbeat" => self.process_hpv_heartbeat(comm_id, src, dest, body_req),
+            "merkle_digest" => self.process_merkle_digest(comm_id, src, dest, body_req),
+            "merkle_leaves" => self.process_merkle_leaves(comm_id, src, dest, body_req),
+            "merkle_values" => self.process_merkle_values(comm_id, src, dest, body_req),
+            "bloom_digest" => self.process_bloom_digest(comm_id, src, dest, body_req),
+            "bloom_values" => self.process_bloom_values(comm_id, src, dest, body_req),
+            "heartbeat" => self.process_heartbeat(comm_id, src, dest, body_req),
+            "push_pull" => self.process_push_pull(comm_id, src, dest, body_req),
+            "push_pull_ok" => self.process_push_pull_ok(comm_id, src, dest, body_req),
+            "causal_broadcast" => self.process_causal_broadcast(comm_id, src, dest, body_req),
+            "causal_broadcast_ok" => self.process_causal_broadcast_ok(comm_id, src, dest, body_req),
+            "ord_submit" => self.process_ord_submit(comm_id, src, dest, body_req),
+            "ord_accept" => self.process_ord_accept(comm_id, src, dest, body_req),
+            "ord_accept_ok" => self.process_ord_accept_ok(comm_id, src, dest, body_req),
+            "ord_commit" => self.process_ord_commit(comm_id, src, dest, body_req),
+            "ord_nack" => self.process_ord_nack(comm_id, src, dest, body_req),
+            "ord_sync" => self.process_ord_sync(comm_id, src, dest, body_req),
+            "ord_takeover" => self.process_ord_takeover(comm_id, src, dest, body_req),
+            "ord_takeover_ok" => self.process_ord_takeover_ok(comm_id, src, dest, body_req),
+            "bracha_send" => self.process_bracha(comm_id, src, dest, body_req),
+            "bracha_echo" => self.process_bracha(comm_id, src, dest, body_req),
+            "bracha_ready" => self.process_bracha(comm_id, src, dest, body_req),
+            "bracha_ok" => self.process_bracha_ok(comm_id, src, dest, body_req),
+            "sync" => self.process_sync(comm_id, src, dest, body_req),
+            "sync_ok" => self.process_sync_ok(comm_id, src, dest, body_req),
             _ => panic!("Unmatched message type"),
         }
     }
 
+    #[cfg(test)]
+    fn write(&self, msg: &str) {
+        self.outbox.lock().unwrap().push(msg.to_string());
+    }
+
+    #[cfg(test)]
+    fn log(&self, _msg: &str) {}
+
+    fn observe_received(&self, src: &NodeId) {
+        if self.config.failure_detector {
+            self.record_arrival(src);
+        }
+    }
+
+    fn observe_sent(&self, dest: &NodeId) {
+        if self.config.failure_detector {
+            self.record_departure(dest);
+        }
+    }
+
     fn next_msg_id(&self) -> MsgId {
         let mut next_msg_id = self.next_msg_id.lock().unwrap();
         let msg_id = *next_msg_id;
@@ -156,23 +701,19 @@ impl Node for MlstService {
         *self.node_id.lock().unwrap() = Some(value)
     }
 
-    fn set_neighbor_ids(&self, values: Vec<NodeId>) {
-        *self.neighbor_ids.lock().unwrap() = values;
+    fn set_node_ids(&self, values: Vec<NodeId>) {
+        *self.node_ids.lock().unwrap() = values;
     }
 
-    fn get_neighbor_ids(&self) -> &Mutex<Vec<NodeId>> {
-        &self.neighbor_ids
-    }
-
-    fn store_message(&self, message: MsgType) {
-        self.messages.lock().unwrap().insert(message);
+    fn get_node_ids(&self) -> &Mutex<Vec<NodeId>> {
+        &self.node_ids
     }
 
-    fn check_message(&self, message: &MsgType) -> bool {
-        self.messages.lock().unwrap().contains(message)
+    fn set_neighbor_ids(&self, values: Vec<NodeId>) {
+        *self.neighbor_ids.lock().unwrap() = values;
     }
 
-    fn get_messages(&self) -> &Mutex<HashSet<MsgType>> {
-        &self.messages
+    fn get_neighbor_ids(&self) -> &Mutex<Vec<NodeId>> {
+        &self.neighbor_ids
     }
 }
@@ -1,11 +1,24 @@
+use crate::interval_set::IntervalSet;
 use crate::node::proto::MlstBodyType;
-use crate::node::{MsgId, Node, NodeId};
-use std::collections::HashMap;
+use crate::node::{MsgId, MsgType, Node, NodeId};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const INITIAL_WINDOW: f64 = 4.0;
+const MAX_WINDOW: f64 = 256.0;
+const MIN_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(200);
+// an ack this many times slower than the smoothed RTT counts as a congestion signal
+const SLOW_ACK_FACTOR: f64 = 3.0;
+// a message retransmitted this often stops taking a window slot, so a peer that never acks
+// it cannot hold back everything queued behind it; it is still retransmitted
+const MAX_WINDOW_RETRIES: u32 = 8;
 
 #[derive(Clone)]
 pub struct MsgCached {
     pub msg_str: String,
+    pub sent_at: Option<Instant>,
+    pub retries: u32,
 }
 
 #[derive(Hash, Eq, PartialEq)]
@@ -14,28 +27,250 @@ pub struct MsgCachedKey {
     pub dest: NodeId,
 }
 
+// AIMD congestion window of one destination. Messages beyond the window wait in `queue`
+// and only move to the pending (in flight) set once acks free up room. What is in flight is
+// counted from the pending set itself, so a resend under a still pending id takes no slot,
+// and neither does a message that exhausted its window retries.
+pub struct PeerWindow {
+    pub cwnd: f64,
+    pub queue: VecDeque<(MsgId, MsgCached)>,
+    pub srtt_ms: Option<f64>,
+    pub last_decrease: Option<Instant>,
+}
+
+impl Default for PeerWindow {
+    fn default() -> Self {
+        Self {
+            cwnd: INITIAL_WINDOW,
+            queue: VecDeque::new(),
+            srtt_ms: None,
+            last_decrease: None,
+        }
+    }
+}
+
+impl PeerWindow {
+    fn has_room(&self, in_flight: usize) -> bool {
+        (in_flight as f64) < self.cwnd.floor()
+    }
+
+    pub fn retransmit_timeout(&self) -> Duration {
+        self.srtt_ms
+            .map_or(MIN_RETRANSMIT_TIMEOUT, |srtt| {
+                Duration::from_secs_f64(2.0 * srtt / 1000.0)
+            })
+            .max(MIN_RETRANSMIT_TIMEOUT)
+    }
+
+    fn increase(&mut self) {
+        self.cwnd = (self.cwnd + 1.0 / self.cwnd).min(MAX_WINDOW);
+    }
+
+    // At most once per RTT, so one burst of losses halves the window only once.
+    fn decrease(&mut self, now: Instant) {
+        let rtt = self.retransmit_timeout() / 2;
+        if self
+            .last_decrease
+            .is_some_and(|at| now.duration_since(at) < rtt)
+        {
+            return;
+        }
+        self.cwnd = (self.cwnd / 2.0).max(1.0);
+        self.last_decrease = Some(now);
+    }
+}
+
+fn in_flight(unacked: &HashMap<MsgCachedKey, MsgCached>, dest: &NodeId) -> usize {
+    unacked
+        .iter()
+        .filter(|(key, msg_cached)| &key.dest == dest && msg_cached.retries < MAX_WINDOW_RETRIES)
+        .count()
+}
+
 pub trait AsyncCommNode: Node {
     fn await_communicate(&self, msg_id: MsgId, dest: NodeId, msg: impl serde::Serialize) {
         let msg_cached = MsgCached {
             msg_str: serde_json::to_string(&msg).unwrap(),
+            sent_at: None,
+            retries: 0,
         };
         let key = MsgCachedKey { msg_id, dest };
+        {
+            let unacked = self.get_pending_ack_ids().lock().unwrap();
+            let mut windows = self.get_peer_windows().lock().unwrap();
+            let window = windows.entry(key.dest.to_owned()).or_default();
+            if !unacked.contains_key(&key) && !window.has_room(in_flight(&unacked, &key.dest)) {
+                window.queue.push_back((key.msg_id, msg_cached));
+                return;
+            }
+        }
         self.ack_await(key, msg_cached);
     }
 
+    // Sends what was never sent and retransmits what timed out, halving the window on loss.
     fn repeat_unacked(&self) {
-        let unacked = self.get_pending_ack_ids().lock().unwrap();
-        for (key, msg_cached) in &*unacked {
-            let dest = key.dest.to_owned();
-            let raw_val =
-                serde_json::value::RawValue::from_string(msg_cached.msg_str.to_owned()).unwrap();
-            self.communicate(dest, MlstBodyType::<u8>::Comm(raw_val));
+        let now = Instant::now();
+        let mut stalled = Vec::new();
+        {
+            let mut unacked = self.get_pending_ack_ids().lock().unwrap();
+            let mut windows = self.get_peer_windows().lock().unwrap();
+            for (key, msg_cached) in unacked.iter_mut() {
+                if self.skip_resend_to(&key.dest) {
+                    continue;
+                }
+                let window = windows.entry(key.dest.to_owned()).or_default();
+                if let Some(sent_at) = msg_cached.sent_at {
+                    if now.duration_since(sent_at) < window.retransmit_timeout() {
+                        continue;
+                    }
+                    window.decrease(now);
+                    msg_cached.retries += 1;
+                    if msg_cached.retries == MAX_WINDOW_RETRIES {
+                        stalled.push(key.dest.to_owned());
+                    }
+                }
+                msg_cached.sent_at = Some(now);
+                let dest = key.dest.to_owned();
+                let raw_val =
+                    serde_json::value::RawValue::from_string(msg_cached.msg_str.to_owned())
+                        .unwrap();
+                self.communicate(dest, MlstBodyType::<u8>::Comm(raw_val));
+            }
+        }
+        for dest in stalled {
+            self.release_queued(&dest);
         }
     }
 
+    // Adapt the window to the ack and let queued messages into the freed slots.
+    fn window_acked(&self, key: &MsgCachedKey, msg_cached: &MsgCached) {
+        let now = Instant::now();
+        {
+            let mut windows = self.get_peer_windows().lock().unwrap();
+            let window = windows.entry(key.dest.to_owned()).or_default();
+            // Karn: RTT samples of retransmitted messages are ambiguous
+            match msg_cached.sent_at {
+                Some(sent_at) if msg_cached.retries == 0 => {
+                    let sample_ms = now.duration_since(sent_at).as_secs_f64() * 1000.0;
+                    let srtt_ms = window.srtt_ms.unwrap_or(sample_ms);
+                    if sample_ms > SLOW_ACK_FACTOR * srtt_ms {
+                        window.decrease(now);
+                    } else {
+                        window.increase();
+                    }
+                    window.srtt_ms = Some(0.875 * srtt_ms + 0.125 * sample_ms);
+                }
+                _ => window.increase(),
+            }
+        }
+        self.release_queued(&key.dest);
+    }
+
+    fn release_queued(&self, dest: &NodeId) {
+        let mut released = Vec::new();
+        {
+            let unacked = self.get_pending_ack_ids().lock().unwrap();
+            let mut windows = self.get_peer_windows().lock().unwrap();
+            let Some(window) = windows.get_mut(dest) else {
+                return;
+            };
+            let in_flight = in_flight(&unacked, dest);
+            while window.has_room(in_flight + released.len()) {
+                match window.queue.pop_front() {
+                    Some(queued) => released.push(queued),
+                    None => break,
+                }
+            }
+        }
+        for (msg_id, queued) in released {
+            let key = MsgCachedKey {
+                msg_id,
+                dest: dest.to_owned(),
+            };
+            self.ack_await(key, queued);
+        }
+    }
+
+    // Drops everything still in flight or queued for a peer we no longer talk to.
+    fn forget_peer(&self, peer: &NodeId) {
+        let mut unacked = self.get_pending_ack_ids().lock().unwrap();
+        let mut windows = self.get_peer_windows().lock().unwrap();
+        let before = unacked.len();
+        unacked.retain(|key, _| &key.dest != peer);
+        let queued = windows.remove(peer).map_or(0, |window| window.queue.len());
+        self.log(&format!(
+            "forgot {} pending and {} queued messages for {}",
+            before - unacked.len(),
+            queued,
+            peer
+        ));
+    }
+
+    // Lets a failure detector stop retries towards peers that look dead.
+    fn skip_resend_to(&self, _dest: &NodeId) -> bool {
+        false
+    }
+
     fn get_pending_ack_ids(&self) -> &Mutex<HashMap<MsgCachedKey, MsgCached>>;
 
+    fn get_peer_windows(&self) -> &Mutex<HashMap<NodeId, PeerWindow>>;
+
     fn ack_await(&self, key: MsgCachedKey, msg_cached: MsgCached);
 
     fn ack_delivered(&self, key: &MsgCachedKey);
+
+    fn store_message(&self, message: MsgType);
+
+    fn store_messages(&self, messages: &IntervalSet) {
+        self.get_messages().lock().unwrap().extend(messages);
+    }
+
+    fn check_message(&self, message: &MsgType) -> bool;
+
+    fn get_messages(&self) -> &Mutex<IntervalSet>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BroadcastMode, Config};
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+
+    // A peer that never acks ends up with every slot taken by retries; once those run out
+    // of retries, what is queued behind them gets its turn.
+    #[test]
+    fn exhausted_retries_free_their_window_slots() {
+        let mut cluster = Cluster::new(&["n1", "n2"], || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Flood))
+        });
+        cluster.set_topology(&[("n1", &["n2"]), ("n2", &["n1"])]);
+        cluster.down.insert("n2".to_string());
+        for value in 0..10 {
+            let broadcast = json!({"type": "broadcast", "msg_id": value, "message": value});
+            cluster.request("n1", broadcast);
+        }
+        cluster.step();
+        let node = &cluster.nodes["n1"];
+        let queued = |node: &MlstService| node.peer_windows.lock().unwrap()["n2"].queue.len();
+        let in_flight = node.pending_ack_ids.lock().unwrap().len();
+        assert_eq!(in_flight, INITIAL_WINDOW as usize);
+        assert_eq!(queued(node), 10 - in_flight);
+
+        // each round the slots taken by entries about to run out of retries open up, even
+        // though the timeouts also shrink the window
+        for _ in 0..10 {
+            let long_ago = Instant::now() - Duration::from_secs(10);
+            let node = &cluster.nodes["n1"];
+            for msg_cached in node.pending_ack_ids.lock().unwrap().values_mut() {
+                msg_cached.sent_at = Some(long_ago);
+                msg_cached.retries = msg_cached.retries.max(MAX_WINDOW_RETRIES - 1);
+            }
+            cluster.step();
+        }
+        let node = &cluster.nodes["n1"];
+        assert_eq!(node.pending_ack_ids.lock().unwrap().len(), 10);
+        assert_eq!(queued(node), 0);
+    }
 }
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+// Words are 32 bits wide so they stay exact numbers on the JSON wire.
+#[derive(Serialize, Deserialize)]
+pub struct BloomFilter {
+    pub bits: Vec<u32>,
+    pub hashes: u32,
+    // Fresh per filter, so a false positive in one round is unlikely to repeat in the next.
+    pub seed: u32,
+}
+
+impl BloomFilter {
+    pub fn with_capacity(items: usize, false_positive_rate: f64, seed: u32) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-items * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(32.0);
+        let hashes = ((bit_count / items) * ln2).round().max(1.0) as u32;
+        Self {
+            bits: vec![0; (bit_count as usize).div_ceil(32)],
+            hashes,
+            seed,
+        }
+    }
+
+    pub fn insert(&mut self, item: &impl Hash) {
+        for bit in self.bit_indexes(item) {
+            self.bits[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+
+    pub fn contains(&self, item: &impl Hash) -> bool {
+        self.bit_indexes(item)
+            .into_iter()
+            .all(|bit| self.bits[bit / 32] & (1 << (bit % 32)) != 0)
+    }
+
+    // Double hashing: the i-th index is h1 + i * h2.
+    fn bit_indexes(&self, item: &impl Hash) -> Vec<usize> {
+        let mut hasher = DefaultHasher::new();
+        (self.seed, item).hash(&mut hasher);
+        let h1 = hasher.finish();
+        (self.seed, item, h1).hash(&mut hasher);
+        let h2 = hasher.finish() | 1;
+        let bit_count = (self.bits.len() * 32) as u64;
+        (0..self.hashes as u64)
+            .map(|i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_count) as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserted_items_are_always_contained() {
+        for seed in 0..8 {
+            let mut filter = BloomFilter::with_capacity(1000, 0.01, seed);
+            for item in 0..1000i64 {
+                filter.insert(&item);
+            }
+            assert!((0..1000i64).all(|item| filter.contains(&item)));
+        }
+    }
+
+    #[test]
+    fn false_positive_rate_stays_near_the_target() {
+        let mut filter = BloomFilter::with_capacity(1000, 0.01, 7);
+        for item in 0..1000i64 {
+            filter.insert(&item);
+        }
+        let false_positives = (1000..11000i64).filter(|item| filter.contains(item)).count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn empty_filter_contains_nothing() {
+        let filter = BloomFilter::with_capacity(0, 0.01, 1);
+        assert!(!(0..100i64).any(|item| filter.contains(&item)));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::node::{MsgType, NodeId};
+use std::collections::{HashMap, HashSet};
+
+// One broadcast instance is identified by its origin and the origin's sequence number.
+pub type BrachaId = (NodeId, u64);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BrachaAction {
+    Echo(MsgType),
+    Ready(MsgType),
+    Deliver(MsgType),
+}
+
+#[derive(Default)]
+struct BrachaInstance {
+    echoes: HashMap<MsgType, HashSet<NodeId>>,
+    readies: HashMap<MsgType, HashSet<NodeId>>,
+    // every node gets to echo and ready once per instance, whatever values it equivocates on
+    echoed_by: HashSet<NodeId>,
+    readied_by: HashSet<NodeId>,
+    echoed: bool,
+    readied: bool,
+    delivered: bool,
+}
+
+// Bracha's reliable broadcast as a plain state machine: feed it the SEND/ECHO/READY messages
+// received (with the authenticated sender) and perform the actions it returns. With n >= 3f+1
+// nodes of which at most f are faulty, all honest nodes deliver the same value or none at all.
+// Being free of I/O, it can be driven in-process by hand-crafted faulty peers.
+pub struct Bracha {
+    n: usize,
+    f: usize,
+    instances: HashMap<BrachaId, BrachaInstance>,
+}
+
+impl Bracha {
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            f: n.saturating_sub(1) / 3,
+            instances: HashMap::new(),
+        }
+    }
+
+    fn echo_quorum(&self) -> usize {
+        (self.n + self.f + 1).div_ceil(2)
+    }
+
+    // Only the origin itself may SEND within its instance.
+    pub fn on_send(&mut self, id: &BrachaId, from: &NodeId, value: MsgType) -> Vec<BrachaAction> {
+        if from != &id.0 {
+            return Vec::new();
+        }
+        let instance = self.instances.entry(id.to_owned()).or_default();
+        if instance.echoed {
+            return Vec::new();
+        }
+        instance.echoed = true;
+        vec![BrachaAction::Echo(value)]
+    }
+
+    pub fn on_echo(&mut self, id: &BrachaId, from: &NodeId, value: MsgType) -> Vec<BrachaAction> {
+        let echo_quorum = self.echo_quorum();
+        let instance = self.instances.entry(id.to_owned()).or_default();
+        if !instance.echoed_by.insert(from.to_owned()) {
+            return Vec::new();
+        }
+        let echoes = instance.echoes.entry(value).or_default();
+        echoes.insert(from.to_owned());
+        if echoes.len() < echo_quorum || instance.readied {
+            return Vec::new();
+        }
+        instance.readied = true;
+        vec![BrachaAction::Ready(value)]
+    }
+
+    pub fn on_ready(&mut self, id: &BrachaId, from: &NodeId, value: MsgType) -> Vec<BrachaAction> {
+        let f = self.f;
+        let instance = self.instances.entry(id.to_owned()).or_default();
+        if !instance.readied_by.insert(from.to_owned()) {
+            return Vec::new();
+        }
+        let readies = instance.readies.entry(value).or_default();
+        readies.insert(from.to_owned());
+        let count = readies.len();
+        let mut actions = Vec::new();
+        // f+1 READYs mean at least one honest node saw an echo quorum
+        if count > f && !instance.readied {
+            instance.readied = true;
+            actions.push(BrachaAction::Ready(value));
+        }
+        if count > 2 * f && !instance.delivered {
+            instance.delivered = true;
+            actions.push(BrachaAction::Deliver(value));
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use Behaviour::{Equivocate, Honest, Silent};
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Phase {
+        Send,
+        Echo,
+        Ready,
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Behaviour {
+        Honest,
+        Silent,
+        // tells every other peer a different value, like MLST_BYZANTINE=equivocate
+        Equivocate,
+    }
+
+    // n nodes exchanging messages in-process, in FIFO order or last in first out.
+    struct Cluster {
+        node_ids: Vec<NodeId>,
+        behaviours: Vec<Behaviour>,
+        protocols: Vec<Bracha>,
+        queue: VecDeque<(usize, usize, Phase, MsgType)>,
+        delivered: Vec<Vec<MsgType>>,
+    }
+
+    impl Cluster {
+        fn new(behaviours: Vec<Behaviour>) -> Self {
+            let n = behaviours.len();
+            Self {
+                node_ids: (0..n).map(|i| format!("n{}", i)).collect(),
+                behaviours,
+                protocols: (0..n).map(|_| Bracha::new(n)).collect(),
+                queue: VecDeque::new(),
+                delivered: vec![Vec::new(); n],
+            }
+        }
+
+        fn multicast(&mut self, from: usize, phase: Phase, value: MsgType) {
+            let behaviour = self.behaviours[from];
+            if behaviour == Behaviour::Silent && phase != Phase::Send {
+                return;
+            }
+            for to in 0..self.node_ids.len() {
+                let value = match behaviour {
+                    Behaviour::Equivocate if to % 2 == 1 => !value,
+                    _ => value,
+                };
+                self.queue.push_back((from, to, phase, value));
+            }
+        }
+
+        fn run(&mut self, origin: usize, value: MsgType, lifo: bool) {
+            self.multicast(origin, Phase::Send, value);
+            let id = (self.node_ids[origin].to_owned(), 1);
+            loop {
+                let next = if lifo {
+                    self.queue.pop_back()
+                } else {
+                    self.queue.pop_front()
+                };
+                let Some((from, to, phase, value)) = next else {
+                    break;
+                };
+                let from_id = self.node_ids[from].to_owned();
+                let protocol = &mut self.protocols[to];
+                let actions = match phase {
+                    Phase::Send => protocol.on_send(&id, &from_id, value),
+                    Phase::Echo => protocol.on_echo(&id, &from_id, value),
+                    Phase::Ready => protocol.on_ready(&id, &from_id, value),
+                };
+                for action in actions {
+                    match action {
+                        BrachaAction::Echo(value) => self.multicast(to, Phase::Echo, value),
+                        BrachaAction::Ready(value) => self.multicast(to, Phase::Ready, value),
+                        BrachaAction::Deliver(value) => self.delivered[to].push(value),
+                    }
+                }
+            }
+        }
+
+        fn honest_deliveries(&self) -> Vec<&Vec<MsgType>> {
+            (0..self.node_ids.len())
+                .filter(|i| self.behaviours[*i] == Behaviour::Honest)
+                .map(|i| &self.delivered[i])
+                .collect()
+        }
+    }
+
+    #[test]
+    fn honest_nodes_deliver_despite_a_silent_peer() {
+        for lifo in [false, true] {
+            let mut cluster = Cluster::new(vec![Honest, Honest, Honest, Silent]);
+            cluster.run(0, 7, lifo);
+            for delivered in cluster.honest_deliveries() {
+                assert_eq!(delivered, &vec![7]);
+            }
+        }
+    }
+
+    #[test]
+    fn honest_nodes_deliver_despite_an_equivocating_peer() {
+        for lifo in [false, true] {
+            let mut cluster = Cluster::new(vec![Honest, Equivocate, Honest, Honest]);
+            cluster.run(0, 7, lifo);
+            for delivered in cluster.honest_deliveries() {
+                assert_eq!(delivered, &vec![7]);
+            }
+        }
+    }
+
+    #[test]
+    fn an_equivocating_origin_cannot_split_honest_nodes() {
+        for lifo in [false, true] {
+            let mut cluster = Cluster::new(vec![Equivocate, Honest, Honest, Honest]);
+            cluster.run(0, 7, lifo);
+            let deliveries = cluster.honest_deliveries();
+            assert!(deliveries.iter().all(|delivered| delivered.len() <= 1));
+            assert!(deliveries.windows(2).all(|pair| pair[0] == pair[1]));
+        }
+    }
+
+    #[test]
+    fn delivery_needs_2f_plus_1_readies() {
+        let id = ("n0".to_string(), 1);
+        let mut protocol = Bracha::new(4);
+        assert!(protocol.on_ready(&id, &"n1".to_string(), 7).is_empty());
+        assert_eq!(
+            protocol.on_ready(&id, &"n2".to_string(), 7),
+            vec![BrachaAction::Ready(7)]
+        );
+        assert_eq!(
+            protocol.on_ready(&id, &"n3".to_string(), 7),
+            vec![BrachaAction::Deliver(7)]
+        );
+    }
+
+    #[test]
+    fn f_faulty_peers_alone_change_nothing() {
+        let id = ("n0".to_string(), 1);
+        let faulty = "n3".to_string();
+        let mut protocol = Bracha::new(4);
+        // not the origin, so its SEND is ignored
+        assert!(protocol.on_send(&id, &faulty, 9).is_empty());
+        assert!(protocol.on_echo(&id, &faulty, 9).is_empty());
+        assert!(protocol.on_ready(&id, &faulty, 9).is_empty());
+        // repeating itself, even with other values, does not add votes
+        assert!(protocol.on_echo(&id, &faulty, 9).is_empty());
+        assert!(protocol.on_ready(&id, &faulty, 9).is_empty());
+        assert!(protocol.on_ready(&id, &faulty, 10).is_empty());
+    }
+}
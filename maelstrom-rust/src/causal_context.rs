@@ -0,0 +1,131 @@
+use crate::node::NodeId;
+use crate::vclock::VectorClock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+// Identifies one event: the node that made it and that node's running count of events.
+pub type Dot = (NodeId, u64);
+
+// Every dot a replica has seen. Dots contiguous from a node's first one are folded into the
+// version vector; only the rest, learnt out of order through deltas, stay in the cloud.
+// Usually the cloud is empty and the context is as small as a vector clock.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct CausalContext {
+    pub compact: VectorClock,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub cloud: BTreeSet<Dot>,
+}
+
+impl CausalContext {
+    pub fn contains(&self, dot: &Dot) -> bool {
+        dot.1 <= self.compact.get(&dot.0) || self.cloud.contains(dot)
+    }
+
+    // A fresh dot for an event of `node_id`, recorded as seen.
+    pub fn next_dot(&mut self, node_id: &NodeId) -> Dot {
+        (node_id.to_owned(), self.compact.increment(node_id))
+    }
+
+    pub fn insert(&mut self, dot: Dot) {
+        self.cloud.insert(dot);
+        self.compact();
+    }
+
+    pub fn merge(&mut self, other: &CausalContext) {
+        for (node_id, value) in other.compact.0.iter() {
+            if *value > self.compact.get(node_id) {
+                self.compact.set(node_id, *value);
+            }
+        }
+        self.cloud.extend(other.cloud.iter().cloned());
+        self.compact();
+    }
+
+    // The cloud is ordered by node and then sequence, so one pass folds every run.
+    fn compact(&mut self) {
+        for dot in std::mem::take(&mut self.cloud) {
+            let current = self.compact.get(&dot.0);
+            if dot.1 == current + 1 {
+                self.compact.set(&dot.0, dot.1);
+            } else if dot.1 > current {
+                self.cloud.insert(dot);
+            }
+        }
+    }
+}
+
+// Join of the dots two replicas hold for the same entry. A dot survives if both hold it, or
+// if one holds it and the other has not seen it yet; a dot the other has seen but dropped
+// was removed there.
+pub fn join_dots(
+    ours: &BTreeSet<Dot>,
+    our_context: &CausalContext,
+    theirs: &BTreeSet<Dot>,
+    their_context: &CausalContext,
+) -> BTreeSet<Dot> {
+    let kept_ours = ours
+        .iter()
+        .filter(|dot| theirs.contains(*dot) || !their_context.contains(dot));
+    let new_theirs = theirs.iter().filter(|dot| !our_context.contains(dot));
+    kept_ours.chain(new_theirs).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(node_id: &str, seq: u64) -> Dot {
+        (node_id.to_string(), seq)
+    }
+
+    #[test]
+    fn out_of_order_dots_fold_once_the_gap_fills() {
+        let mut context = CausalContext::default();
+        context.insert(dot("n1", 3));
+        context.insert(dot("n1", 2));
+        context.insert(dot("n2", 2));
+        assert_eq!(context.compact.get(&"n1".to_string()), 0);
+        assert_eq!(context.cloud.len(), 3);
+        assert!(context.contains(&dot("n1", 3)));
+        assert!(!context.contains(&dot("n1", 1)));
+        context.insert(dot("n1", 1));
+        assert_eq!(context.compact.get(&"n1".to_string()), 3);
+        assert_eq!(context.cloud, BTreeSet::from([dot("n2", 2)]));
+    }
+
+    #[test]
+    fn merge_folds_the_cloud_into_the_other_side_compact() {
+        let mut ours = CausalContext::default();
+        ours.insert(dot("n1", 2));
+        ours.insert(dot("n1", 4));
+        let mut theirs = CausalContext::default();
+        for _ in 0..3 {
+            theirs.next_dot(&"n1".to_string());
+        }
+        ours.merge(&theirs);
+        assert_eq!(ours.compact.get(&"n1".to_string()), 4);
+        assert!(ours.cloud.is_empty());
+        // the other way round gives the same context
+        let mut cloud = CausalContext::default();
+        cloud.insert(dot("n1", 4));
+        cloud.insert(dot("n1", 2));
+        theirs.merge(&cloud);
+        assert_eq!(theirs, ours);
+    }
+
+    #[test]
+    fn join_dots_drops_what_the_other_side_removed() {
+        let mut ours = CausalContext::default();
+        let kept = ours.next_dot(&"n1".to_string());
+        let removed = ours.next_dot(&"n1".to_string());
+        let mut theirs = ours.to_owned();
+        let added = theirs.next_dot(&"n2".to_string());
+        let joined = join_dots(
+            &BTreeSet::from([kept.to_owned(), removed]),
+            &ours,
+            &BTreeSet::from([kept.to_owned(), added.to_owned()]),
+            &theirs,
+        );
+        assert_eq!(joined, BTreeSet::from([kept, added]));
+    }
+}
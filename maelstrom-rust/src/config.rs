@@ -0,0 +1,127 @@
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+const DEFAULT_GOSSIP_FANOUT: usize = 3;
+const DEFAULT_GOSSIP_INTERVAL_MS: u64 = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BroadcastMode {
+    Flood,
+    Plumtree,
+    PushPull,
+    Causal,
+    Ordered,
+    Bracha,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MembershipMode {
+    Topology,
+    HyParView,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AntiEntropyMode {
+    Off,
+    Merkle,
+    Bloom,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ByzantineMode {
+    Honest,
+    // never echoes nor readies anything
+    Silent,
+    // tells every other peer a different value
+    Equivocate,
+}
+
+pub struct Config {
+    pub broadcast: BroadcastMode,
+    pub membership: MembershipMode,
+    pub anti_entropy: AntiEntropyMode,
+    pub failure_detector: bool,
+    pub byzantine: ByzantineMode,
+    pub gossip_fanout: usize,
+    pub gossip_interval: Duration,
+}
+
+impl Config {
+    // Read from the environment, since maelstrom starts the node binary without arguments.
+    pub fn from_env() -> Self {
+        let broadcast = match env::var("MLST_BROADCAST").as_deref() {
+            Ok("plumtree") => BroadcastMode::Plumtree,
+            Ok("push-pull") => BroadcastMode::PushPull,
+            Ok("causal") => BroadcastMode::Causal,
+            Ok("ordered") => BroadcastMode::Ordered,
+            Ok("bracha") => BroadcastMode::Bracha,
+            Ok("flood") | Err(_) => BroadcastMode::Flood,
+            Ok(other) => panic!("Unknown broadcast mode: {}", other),
+        };
+        let membership = match env::var("MLST_MEMBERSHIP").as_deref() {
+            Ok("hyparview") => MembershipMode::HyParView,
+            Ok("topology") | Err(_) => MembershipMode::Topology,
+            Ok(other) => panic!("Unknown membership mode: {}", other),
+        };
+        let anti_entropy = match env::var("MLST_ANTI_ENTROPY").as_deref() {
+            Ok("merkle") => AntiEntropyMode::Merkle,
+            Ok("bloom") => AntiEntropyMode::Bloom,
+            Ok("off") | Err(_) => AntiEntropyMode::Off,
+            Ok(other) => panic!("Unknown anti-entropy mode: {}", other),
+        };
+        // the ordered broadcast only moves the sequencer role on once its holder is suspected
+        let failure_detector = match env::var("MLST_FAILURE_DETECTOR").as_deref() {
+            Ok("on") => true,
+            Ok("off") if broadcast == BroadcastMode::Ordered => {
+                panic!("The ordered broadcast needs the failure detector for sequencer failover")
+            }
+            Ok("off") => false,
+            Err(_) => broadcast == BroadcastMode::Ordered,
+            Ok(other) => panic!("Unknown failure detector setting: {}", other),
+        };
+        let byzantine = match env::var("MLST_BYZANTINE").as_deref() {
+            Ok("silent") => ByzantineMode::Silent,
+            Ok("equivocate") => ByzantineMode::Equivocate,
+            Ok("honest") | Err(_) => ByzantineMode::Honest,
+            Ok(other) => panic!("Unknown byzantine mode: {}", other),
+        };
+        Self {
+            broadcast,
+            membership,
+            anti_entropy,
+            failure_detector,
+            byzantine,
+            gossip_fanout: parse_env("MLST_GOSSIP_FANOUT", DEFAULT_GOSSIP_FANOUT),
+            gossip_interval: Duration::from_millis(parse_env(
+                "MLST_GOSSIP_INTERVAL_MS",
+                DEFAULT_GOSSIP_INTERVAL_MS,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    // What `from_env` gives for `broadcast` with nothing else set.
+    pub fn with_broadcast(broadcast: BroadcastMode) -> Self {
+        Self {
+            broadcast,
+            membership: MembershipMode::Topology,
+            anti_entropy: AntiEntropyMode::Off,
+            failure_detector: broadcast == BroadcastMode::Ordered,
+            byzantine: ByzantineMode::Honest,
+            gossip_fanout: DEFAULT_GOSSIP_FANOUT,
+            gossip_interval: Duration::from_millis(DEFAULT_GOSSIP_INTERVAL_MS),
+        }
+    }
+}
+
+fn parse_env<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid value for {}: {}", name, value)),
+        Err(_) => default,
+    }
+}
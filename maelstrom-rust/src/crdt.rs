@@ -0,0 +1,62 @@
+use crate::node::NodeId;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// maelstrom error codes
+pub const MALFORMED_REQUEST: u32 = 12;
+pub const KEY_DOES_NOT_EXIST: u32 = 20;
+
+// A state based CRDT: replicas exchange their `State`, whole or as deltas, and `merge` it in.
+// Merging must be commutative, associative and idempotent, so states may arrive late, twice
+// or reordered.
+pub trait Crdt: Default {
+    type State: Serialize + DeserializeOwned + Clone;
+    type Value: Serialize;
+
+    fn state(&self) -> Self::State;
+
+    fn merge(&mut self, state: Self::State);
+
+    fn value(&self) -> Self::Value;
+
+    // Delta-state support: the join of all mutations since the last call, as a state small
+    // enough to ship instead of the whole one. None when nothing changed.
+    fn take_delta(&mut self) -> Option<Self::State>;
+}
+
+// The client side of a Maelstrom workload: its requests are applied to the local replica
+// and answered straight away, replication spreads the effect later.
+pub trait CrdtWorkload: Crdt {
+    type Request: DeserializeOwned;
+    type Response: Serialize;
+
+    fn handle(&mut self, node_id: &NodeId, request: Self::Request) -> Self::Response;
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Crdt;
+
+    // A fresh replica with the given states merged in, in order.
+    pub fn joined<C: Crdt>(states: &[&C::State]) -> C {
+        let mut replica = C::default();
+        for state in states {
+            replica.merge((*state).to_owned());
+        }
+        replica
+    }
+
+    fn read<C: Crdt>(states: &[&C::State]) -> serde_json::Value {
+        serde_json::to_value(joined::<C>(states).value()).unwrap()
+    }
+
+    // Replicas must agree whatever order, grouping or repetition the states arrive in.
+    pub fn assert_merge_laws<C: Crdt>(a: &C::State, b: &C::State, c: &C::State) {
+        assert_eq!(read::<C>(&[a, b]), read::<C>(&[b, a]), "merge is not commutative");
+        let ab = joined::<C>(&[a, b]).state();
+        let bc = joined::<C>(&[b, c]).state();
+        assert_eq!(read::<C>(&[&ab, c]), read::<C>(&[a, &bc]), "merge is not associative");
+        assert_eq!(read::<C>(&[a, a]), read::<C>(&[a]), "merge is not idempotent");
+        assert_eq!(read::<C>(&[&ab, a, b]), read::<C>(&[&ab]), "merge is not idempotent");
+    }
+}
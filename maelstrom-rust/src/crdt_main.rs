@@ -1,33 +1,82 @@
 mod node;
+mod causal_context;
+mod crdt;
 mod crdt_node;
+// shared with async-comm-service; the g-set only needs part of it
+#[allow(dead_code)]
+mod interval_set;
+// shared with async-comm-service; the replicate tests only need part of it
+#[cfg(test)]
+#[allow(dead_code)]
+mod sim;
+mod vclock;
+mod crdts {
+    pub mod gcounter;
+    pub mod gset;
+    pub mod lww;
+    pub mod mvregister;
+    pub mod ormap;
+    pub mod orset;
+    pub mod pncounter;
+    pub mod rga;
+}
 mod routes {
-    pub mod read;
     pub mod topology;
     pub mod replicate;
+    pub mod workload;
     pub mod echo;
     pub mod init;
 }
 
-use crate::node::{CommId, MsgId, MsgType, MsgTypeType, Node, NodeId};
-use crate::crdt_node::CrdtNode;
+use crate::node::{CommId, MsgId, MsgTypeType, Node, NodeId};
+use crate::crdt::{Crdt, CrdtWorkload};
+use crate::crdt_node::{CrdtNode, DeltaLog};
+use crate::crdts::gcounter::GCounter;
+use crate::crdts::gset::GSet;
+use crate::crdts::lww::{LwwMap, LwwRegister};
+use crate::crdts::mvregister::MvRegister;
+use crate::crdts::ormap::OrMap;
+use crate::crdts::orset::OrSet;
+use crate::crdts::pncounter::PnCounter;
+use crate::crdts::rga::Rga;
 use crate::routes::replicate::MlstReplicate;
-use crate::routes::read::MlstRead;
+use crate::routes::workload::MlstWorkload;
 use crate::routes::topology::MlstTopology;
 use crate::routes::echo::MlstEcho;
 use crate::routes::init::MlstInit;
-use std::collections::HashSet;
+#[cfg(test)]
+use crate::sim::SimNode;
+use std::env;
 use std::io;
 use std::sync::{Arc, Mutex};
 
+const REPLICATE_INTERVAL_MS: u64 = 500;
+
 #[tokio::main]
 async fn main() -> io::Result<()> {
-    let service = Arc::new(MlstService::new());
+    // maelstrom starts the node binary without arguments, so the CRDT comes from the environment
+    match env::var("MLST_CRDT").as_deref() {
+        Ok("g-counter") => serve::<GCounter>().await,
+        Ok("pn-counter") => serve::<PnCounter>().await,
+        Ok("or-set") => serve::<OrSet>().await,
+        Ok("or-map") => serve::<OrMap>().await,
+        Ok("lww-register") => serve::<LwwRegister>().await,
+        Ok("lww-map") => serve::<LwwMap>().await,
+        Ok("mv-register") => serve::<MvRegister>().await,
+        Ok("rga") => serve::<Rga>().await,
+        Ok("g-set") | Err(_) => serve::<GSet>().await,
+        Ok(other) => panic!("Unknown crdt: {}", other),
+    }
+}
+
+async fn serve<C: CrdtWorkload<State: Send> + Send + 'static>() -> io::Result<()> {
+    let service = Arc::new(MlstService::<C>::new());
     tokio::task::spawn({
         let s = Arc::clone(&service);
         async move {
             loop {
                 s.broadcast();
-                tokio::time::sleep(tokio::time::Duration::from_millis(4999)).await;
+                tokio::time::sleep(tokio::time::Duration::from_millis(REPLICATE_INTERVAL_MS)).await;
             }
         }
     });
@@ -42,62 +91,94 @@ async fn main() -> io::Result<()> {
     Ok(())
 }
 
-struct MlstService {
+struct MlstService<C: Crdt> {
+    #[cfg(test)]
+    pub outbox: Mutex<Vec<String>>,
     pub node_id: Mutex<Option<NodeId>>,
+    pub node_ids: Mutex<Vec<NodeId>>,
     pub neighbor_ids: Mutex<Vec<NodeId>>,
-    pub messages: Mutex<HashSet<MsgType>>,
     pub next_msg_id: Mutex<MsgId>,
+    pub crdt: Mutex<C>,
+    pub delta_log: Mutex<DeltaLog<C::State>>,
 }
 
-impl MlstService {
+impl<C: CrdtWorkload> MlstService<C> {
     pub fn new() -> Self {
         Self {
+            #[cfg(test)]
+            outbox: Mutex::new(Vec::new()),
             node_id: Mutex::new(None),
+            node_ids: Mutex::new(Vec::new()),
             neighbor_ids: Mutex::new(Vec::new()),
-            messages: Mutex::new(HashSet::new()),
             next_msg_id: Mutex::new(1),
+            crdt: Mutex::new(C::default()),
+            delta_log: Mutex::new(DeltaLog::default()),
         }
     }
 }
-impl CrdtNode for MlstService {
+impl<C: CrdtWorkload> CrdtNode for MlstService<C> {
+    type Crdt = C;
+
+    fn get_crdt(&self) -> &Mutex<C> {
+        &self.crdt
+    }
+
+    fn get_delta_log(&self) -> &Mutex<DeltaLog<C::State>> {
+        &self.delta_log
+    }
+
+    fn broadcast(&self) {
+        self.replicate();
+    }
+}
+
+#[cfg(test)]
+impl<C: CrdtWorkload> SimNode for MlstService<C> {
+    fn take_outbox(&self) -> Vec<String> {
+        std::mem::take(&mut self.outbox.lock().unwrap())
+    }
+
+    fn tick(&self) {
+        self.broadcast();
+    }
 }
 
-impl MlstInit for MlstService {
+impl<C: CrdtWorkload> MlstInit for MlstService<C> {
     #[inline]
     fn get_route_init() -> MsgTypeType {
         return "init".to_string();
     }
 }
 
-impl MlstEcho for MlstService {
+impl<C: CrdtWorkload> MlstEcho for MlstService<C> {
     #[inline]
     fn get_route_echo() -> MsgTypeType {
         return "echo".to_string();
     }
 }
 
-impl MlstTopology for MlstService {
+impl<C: CrdtWorkload> MlstTopology for MlstService<C> {
     #[inline]
     fn get_route_topology() -> MsgTypeType {
         return "topology".to_string();
     }
 }
 
-impl MlstRead for MlstService {
-    #[inline]
-    fn get_route_read() -> MsgTypeType {
-        return "read".to_string();
-    }
-}
+impl<C: CrdtWorkload> MlstWorkload for MlstService<C> {}
 
-impl MlstReplicate for MlstService {
+impl<C: CrdtWorkload> MlstReplicate for MlstService<C> {
     #[inline]
     fn get_route_replicate() -> MsgTypeType {
         return "replicate".to_string();
     }
+
+    #[inline]
+    fn get_route_replicate_ok() -> MsgTypeType {
+        return "replicate_ok".to_string();
+    }
 }
 
-impl Node for MlstService {
+impl<C: CrdtWorkload> Node for MlstService<C> {
     fn dispatch_request(
         &self,
         comm_id: Option<CommId>,
@@ -111,12 +192,22 @@ impl Node for MlstService {
             "init" => self.process_init(comm_id, src, dest, body_req),
             "echo" => self.process_echo(comm_id, src, dest, body_req),
             "topology" => self.process_topology(comm_id, src, dest, body_req),
-            "read" => self.process_read(comm_id, src, dest, body_req),
+            "add" | "remove" | "read" | "write" | "insert" | "delete" | "increment"
+            | "remove_key" => self.process_workload(comm_id, src, dest, body_req),
             "replicate" => self.process_replicate(comm_id, src, dest, body_req),
+            "replicate_ok" => self.process_replicate_ok(comm_id, src, dest, body_req),
             _ => panic!("Unmatched message type"),
         }
     }
 
+    #[cfg(test)]
+    fn write(&self, msg: &str) {
+        self.outbox.lock().unwrap().push(msg.to_string());
+    }
+
+    #[cfg(test)]
+    fn log(&self, _msg: &str) {}
+
     fn next_msg_id(&self) -> MsgId {
         let mut next_msg_id = self.next_msg_id.lock().unwrap();
         let msg_id = *next_msg_id;
@@ -132,23 +223,19 @@ impl Node for MlstService {
         *self.node_id.lock().unwrap() = Some(value)
     }
 
-    fn set_neighbor_ids(&self, values: Vec<NodeId>) {
-        *self.neighbor_ids.lock().unwrap() = values;
+    fn set_node_ids(&self, values: Vec<NodeId>) {
+        *self.node_ids.lock().unwrap() = values;
     }
 
-    fn get_neighbor_ids(&self) -> &Mutex<Vec<NodeId>> {
-        &self.neighbor_ids
-    }
-
-    fn store_message(&self, message: MsgType) {
-        self.messages.lock().unwrap().insert(message);
+    fn get_node_ids(&self) -> &Mutex<Vec<NodeId>> {
+        &self.node_ids
     }
 
-    fn check_message(&self, message: &MsgType) -> bool {
-        self.messages.lock().unwrap().contains(message)
+    fn set_neighbor_ids(&self, values: Vec<NodeId>) {
+        *self.neighbor_ids.lock().unwrap() = values;
     }
 
-    fn get_messages(&self) -> &Mutex<HashSet<MsgType>> {
-        &self.messages
+    fn get_neighbor_ids(&self) -> &Mutex<Vec<NodeId>> {
+        &self.neighbor_ids
     }
 }
@@ -1,8 +1,123 @@
-use crate::node::proto::MlstBodyType;
-use crate::node::{MsgId, Node, NodeId};
-use std::collections::HashMap;
+use crate::crdt::Crdt;
+use crate::node::{Node, NodeId};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Mutex;
 
+// Groups are only kept for peers at most this far behind; further back they get the full
+// state, so a peer that never acks does not make the log grow for good.
+const MAX_DELTA_LAG: u64 = 1024;
+
+// Delta groups not yet acked by every peer, keyed by a local sequence number, and how far
+// each peer has acked. Peers that fall behind the oldest group kept get the full state.
+pub struct DeltaLog<S> {
+    pub seq: u64,
+    pub groups: BTreeMap<u64, S>,
+    pub acked: HashMap<NodeId, u64>,
+}
+
+impl<S> Default for DeltaLog<S> {
+    fn default() -> Self {
+        Self {
+            seq: 0,
+            groups: BTreeMap::new(),
+            acked: HashMap::new(),
+        }
+    }
+}
+
+impl<S> DeltaLog<S> {
+    // Drops the groups every peer has acked, and any older than MAX_DELTA_LAG.
+    pub fn prune(&mut self, peers: &[NodeId]) {
+        let all_acked = peers
+            .iter()
+            .map(|p| self.acked.get(p).copied().unwrap_or(0))
+            .min()
+            .unwrap_or(self.seq);
+        let kept_from = all_acked.max(self.seq.saturating_sub(MAX_DELTA_LAG)) + 1;
+        self.groups = self.groups.split_off(&kept_from);
+    }
+}
+
+// A node replicating one CRDT instance; whatever state arrives is merged into it.
 pub trait CrdtNode: Node {
+    type Crdt: Crdt;
+
+    fn get_crdt(&self) -> &Mutex<Self::Crdt>;
+
+    fn get_delta_log(&self) -> &Mutex<DeltaLog<<Self::Crdt as Crdt>::State>>;
+
+    fn crdt_state(&self) -> <Self::Crdt as Crdt>::State {
+        self.get_crdt().lock().unwrap().state()
+    }
+
+    fn merge_state(&self, state: <Self::Crdt as Crdt>::State) {
+        self.get_crdt().lock().unwrap().merge(state);
+    }
+
+    // Applies a local mutation and logs its delta for replication.
+    fn mutate<R>(&self, mutation: impl FnOnce(&mut Self::Crdt) -> R) -> R {
+        let (result, delta) = {
+            let mut crdt = self.get_crdt().lock().unwrap();
+            let result = mutation(&mut crdt);
+            (result, crdt.take_delta())
+        };
+        if let Some(delta) = delta {
+            let mut log = self.get_delta_log().lock().unwrap();
+            log.seq += 1;
+            let seq = log.seq;
+            log.groups.insert(seq, delta);
+        }
+        result
+    }
+
+    fn crdt_peers(&self) -> Vec<NodeId> {
+        let node_id = self.get_node_id().lock().unwrap().to_owned();
+        self.get_node_ids()
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| Some(*p) != node_id.as_ref())
+            .cloned()
+            .collect()
+    }
+
+    // Called periodically to share the local state with the other replicas.
     fn broadcast(&self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DeltaLog, MAX_DELTA_LAG};
+    use crate::node::NodeId;
+
+    fn log_of(seq: u64) -> DeltaLog<u64> {
+        DeltaLog {
+            seq,
+            groups: (1..=seq).map(|s| (s, s)).collect(),
+            ..DeltaLog::default()
+        }
+    }
+
+    #[test]
+    fn groups_go_once_every_peer_acked_them() {
+        let peers: Vec<NodeId> = vec!["n2".to_string(), "n3".to_string()];
+        let mut log = log_of(10);
+        log.acked.insert("n2".to_string(), 10);
+        log.acked.insert("n3".to_string(), 4);
+        log.prune(&peers);
+        assert_eq!(log.groups.keys().copied().collect::<Vec<_>>(), (5..=10).collect::<Vec<_>>());
+        log.acked.insert("n3".to_string(), 10);
+        log.prune(&peers);
+        assert!(log.groups.is_empty());
+    }
+
+    #[test]
+    fn a_silent_peer_is_not_waited_for_beyond_the_lag() {
+        let peers: Vec<NodeId> = vec!["n2".to_string(), "n3".to_string()];
+        let mut log = log_of(3 * MAX_DELTA_LAG);
+        log.acked.insert("n2".to_string(), 3 * MAX_DELTA_LAG);
+        log.prune(&peers);
+        assert_eq!(log.groups.len() as u64, MAX_DELTA_LAG);
+        assert_eq!(log.groups.keys().next(), Some(&(2 * MAX_DELTA_LAG + 1)));
+    }
+}
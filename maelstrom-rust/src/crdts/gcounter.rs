@@ -0,0 +1,107 @@
+use crate::crdt::{Crdt, CrdtWorkload};
+use crate::node::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Grow-only counter: every node only ever raises its own entry, so merging takes the
+// maximum per node and the value is the sum over all of them.
+#[derive(Default)]
+pub struct GCounter {
+    counts: HashMap<NodeId, u64>,
+    delta: HashMap<NodeId, u64>,
+}
+
+impl GCounter {
+    pub fn increment(&mut self, node_id: &NodeId, delta: u64) {
+        let count = self.counts.entry(node_id.to_owned()).or_default();
+        *count += delta;
+        self.delta.insert(node_id.to_owned(), *count);
+    }
+}
+
+impl Crdt for GCounter {
+    type State = HashMap<NodeId, u64>;
+    type Value = u64;
+
+    fn state(&self) -> Self::State {
+        self.counts.to_owned()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        for (node_id, count) in state {
+            let ours = self.counts.entry(node_id).or_default();
+            *ours = (*ours).max(count);
+        }
+    }
+
+    fn value(&self) -> Self::Value {
+        self.counts.values().sum()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        (!self.delta.is_empty()).then(|| std::mem::take(&mut self.delta))
+    }
+}
+
+impl CrdtWorkload for GCounter {
+    type Request = GCounterRequest;
+    type Response = GCounterResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: GCounterRequest) -> GCounterResponse {
+        match request {
+            GCounterRequest::Add { delta } => {
+                self.increment(node_id, delta);
+                GCounterResponse::AddOk
+            }
+            GCounterRequest::Read => GCounterResponse::ReadOk {
+                value: self.value(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GCounterRequest {
+    Add { delta: u64 },
+    Read,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GCounterResponse {
+    AddOk,
+    ReadOk { value: u64 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+
+    #[test]
+    fn increments_of_every_node_are_summed() {
+        let (n1, n2) = ("n1".to_string(), "n2".to_string());
+        let mut counter = GCounter::default();
+        counter.increment(&n1, 2);
+        counter.increment(&n1, 3);
+        counter.increment(&n2, 4);
+        assert_eq!(counter.value(), 9);
+        assert_eq!(counter.state(), HashMap::from([(n1, 5), (n2, 4)]));
+    }
+
+    #[test]
+    fn merge_keeps_the_highest_count_per_node() {
+        let n1 = "n1".to_string();
+        let mut old = GCounter::default();
+        old.increment(&n1, 1);
+        let mut new = joined::<GCounter>(&[&old.state()]);
+        new.increment(&n1, 2);
+        let mut other = GCounter::default();
+        other.increment(&"n2".to_string(), 5);
+        let (old, new, other) = (old.state(), new.state(), other.state());
+        assert_merge_laws::<GCounter>(&old, &new, &other);
+        // n1's later count replaces its earlier one instead of adding to it
+        assert_eq!(joined::<GCounter>(&[&new, &old, &other]).value(), 8);
+    }
+}
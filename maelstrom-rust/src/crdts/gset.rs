@@ -0,0 +1,102 @@
+use crate::crdt::{Crdt, CrdtWorkload};
+use crate::interval_set::IntervalSet;
+use crate::node::{MsgType, NodeId};
+use serde::{Deserialize, Serialize};
+
+// Grow-only set: merging is union. Elements are integers, so the set is range encoded.
+#[derive(Default)]
+pub struct GSet {
+    elements: IntervalSet,
+    delta: IntervalSet,
+}
+
+impl GSet {
+    pub fn add(&mut self, element: MsgType) {
+        if self.elements.insert(element) {
+            self.delta.insert(element);
+        }
+    }
+}
+
+impl Crdt for GSet {
+    type State = IntervalSet;
+    type Value = Vec<MsgType>;
+
+    fn state(&self) -> Self::State {
+        self.elements.to_owned()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        self.elements.extend(&state);
+    }
+
+    fn value(&self) -> Self::Value {
+        self.elements.iter().collect()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        (!self.delta.is_empty()).then(|| std::mem::take(&mut self.delta))
+    }
+}
+
+impl CrdtWorkload for GSet {
+    type Request = GSetRequest;
+    type Response = GSetResponse;
+
+    fn handle(&mut self, _node_id: &NodeId, request: GSetRequest) -> GSetResponse {
+        match request {
+            GSetRequest::Add { element } => {
+                self.add(element);
+                GSetResponse::AddOk
+            }
+            GSetRequest::Read => GSetResponse::ReadOk {
+                value: self.value(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GSetRequest {
+    Add { element: MsgType },
+    Read,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GSetResponse {
+    AddOk,
+    ReadOk { value: Vec<MsgType> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+
+    fn set(elements: &[MsgType]) -> GSet {
+        let mut set = GSet::default();
+        for element in elements {
+            set.add(*element);
+        }
+        set
+    }
+
+    #[test]
+    fn merge_is_union() {
+        let a = set(&[1, 2, 3]).state();
+        let b = set(&[3, 4, 9]).state();
+        let c = set(&[5, 100]).state();
+        assert_merge_laws::<GSet>(&a, &b, &c);
+        assert_eq!(joined::<GSet>(&[&a, &b, &c]).value(), vec![1, 2, 3, 4, 5, 9, 100]);
+    }
+
+    #[test]
+    fn delta_carries_only_new_elements() {
+        let mut set = set(&[1, 2]);
+        assert_eq!(set.take_delta().map(|d| d.len()), Some(2));
+        set.add(2);
+        assert!(set.take_delta().is_none());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::crdt::{Crdt, CrdtWorkload, KEY_DOES_NOT_EXIST};
+use crate::node::{MsgType, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Wall clock milliseconds, ties broken by node id, so any two writes are ordered.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LwwStamp {
+    pub time: u64,
+    pub node_id: NodeId,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LwwEntry {
+    pub stamp: LwwStamp,
+    pub value: MsgType,
+}
+
+// Hands out stamps later than both the local clock and every stamp seen so far, so a write
+// always wins over what it read, even across skewed clocks.
+#[derive(Default)]
+struct LwwClock {
+    last_time: u64,
+}
+
+impl LwwClock {
+    fn stamp(&mut self, node_id: &NodeId) -> LwwStamp {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.last_time = now.max(self.last_time + 1);
+        LwwStamp {
+            time: self.last_time,
+            node_id: node_id.to_owned(),
+        }
+    }
+
+    fn observe(&mut self, stamp: &LwwStamp) {
+        self.last_time = self.last_time.max(stamp.time);
+    }
+
+    fn merge_entry(&mut self, ours: &mut Option<LwwEntry>, theirs: LwwEntry) {
+        self.observe(&theirs.stamp);
+        if ours.as_ref().is_none_or(|entry| entry.stamp < theirs.stamp) {
+            *ours = Some(theirs);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LwwRegister {
+    entry: Option<LwwEntry>,
+    clock: LwwClock,
+    delta: Option<LwwEntry>,
+}
+
+impl LwwRegister {
+    pub fn write(&mut self, node_id: &NodeId, value: MsgType) {
+        let stamp = self.clock.stamp(node_id);
+        self.entry = Some(LwwEntry { stamp, value });
+        self.delta = self.entry.to_owned();
+    }
+}
+
+impl Crdt for LwwRegister {
+    type State = Option<LwwEntry>;
+    type Value = Option<MsgType>;
+
+    fn state(&self) -> Self::State {
+        self.entry.to_owned()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        if let Some(theirs) = state {
+            self.clock.merge_entry(&mut self.entry, theirs);
+        }
+    }
+
+    fn value(&self) -> Self::Value {
+        self.entry.as_ref().map(|entry| entry.value)
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        self.delta.take().map(Some)
+    }
+}
+
+impl CrdtWorkload for LwwRegister {
+    type Request = LwwRegisterRequest;
+    type Response = LwwResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: LwwRegisterRequest) -> LwwResponse {
+        match request {
+            LwwRegisterRequest::Write { value } => {
+                self.write(node_id, value);
+                LwwResponse::WriteOk
+            }
+            LwwRegisterRequest::Read => match self.value() {
+                Some(value) => LwwResponse::ReadOk { value },
+                None => LwwResponse::key_does_not_exist(),
+            },
+        }
+    }
+}
+
+// A register per key; keys merge independently.
+#[derive(Default)]
+pub struct LwwMap {
+    entries: BTreeMap<MsgType, LwwEntry>,
+    clock: LwwClock,
+    delta: BTreeMap<MsgType, LwwEntry>,
+}
+
+impl LwwMap {
+    pub fn write(&mut self, node_id: &NodeId, key: MsgType, value: MsgType) {
+        let stamp = self.clock.stamp(node_id);
+        let entry = LwwEntry { stamp, value };
+        self.delta.insert(key, entry.to_owned());
+        self.entries.insert(key, entry);
+    }
+
+    pub fn get(&self, key: &MsgType) -> Option<MsgType> {
+        self.entries.get(key).map(|entry| entry.value)
+    }
+}
+
+impl Crdt for LwwMap {
+    type State = BTreeMap<MsgType, LwwEntry>;
+    type Value = BTreeMap<MsgType, MsgType>;
+
+    fn state(&self) -> Self::State {
+        self.entries.to_owned()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        for (key, theirs) in state {
+            let mut ours = self.entries.remove(&key);
+            self.clock.merge_entry(&mut ours, theirs);
+            self.entries.extend(ours.map(|entry| (key, entry)));
+        }
+    }
+
+    fn value(&self) -> Self::Value {
+        self.entries
+            .iter()
+            .map(|(key, entry)| (*key, entry.value))
+            .collect()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        (!self.delta.is_empty()).then(|| std::mem::take(&mut self.delta))
+    }
+}
+
+impl CrdtWorkload for LwwMap {
+    type Request = LwwMapRequest;
+    type Response = LwwResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: LwwMapRequest) -> LwwResponse {
+        match request {
+            LwwMapRequest::Write { key, value } => {
+                self.write(node_id, key, value);
+                LwwResponse::WriteOk
+            }
+            LwwMapRequest::Read { key } => match self.get(&key) {
+                Some(value) => LwwResponse::ReadOk { value },
+                None => LwwResponse::key_does_not_exist(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LwwRegisterRequest {
+    Write { value: MsgType },
+    Read,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LwwMapRequest {
+    Write { key: MsgType, value: MsgType },
+    Read { key: MsgType },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LwwResponse {
+    WriteOk,
+    ReadOk { value: MsgType },
+    Error { code: u32, text: String },
+}
+
+impl LwwResponse {
+    fn key_does_not_exist() -> Self {
+        LwwResponse::Error {
+            code: KEY_DOES_NOT_EXIST,
+            text: "key does not exist".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+
+    fn entry(time: u64, node_id: &str, value: MsgType) -> LwwEntry {
+        LwwEntry {
+            stamp: LwwStamp {
+                time,
+                node_id: node_id.to_string(),
+            },
+            value,
+        }
+    }
+
+    #[test]
+    fn register_keeps_the_latest_stamp() {
+        let a = Some(entry(10, "n1", 1));
+        let b = Some(entry(10, "n2", 2));
+        let c = Some(entry(5, "n3", 3));
+        assert_merge_laws::<LwwRegister>(&a, &b, &c);
+        assert_eq!(joined::<LwwRegister>(&[&a, &b, &c]).value(), Some(2));
+    }
+
+    #[test]
+    fn equal_times_fall_back_to_node_id() {
+        let lower = Some(entry(10, "n1", 1));
+        let higher = Some(entry(10, "n2", 2));
+        assert_eq!(joined::<LwwRegister>(&[&lower, &higher]).value(), Some(2));
+        assert_eq!(joined::<LwwRegister>(&[&higher, &lower]).value(), Some(2));
+        let mut map = joined::<LwwMap>(&[&BTreeMap::from([(1, entry(10, "n2", 2))])]);
+        map.merge(BTreeMap::from([(1, entry(10, "n1", 1))]));
+        assert_eq!(map.get(&1), Some(2));
+    }
+
+    #[test]
+    fn write_wins_over_a_merged_future_stamp() {
+        let far = u64::MAX / 2;
+        let mut register = joined::<LwwRegister>(&[&Some(entry(far, "n2", 1))]);
+        register.write(&"n1".to_string(), 2);
+        assert_eq!(register.value(), Some(2));
+    }
+
+    #[test]
+    fn map_merges_keys_apart() {
+        let a = BTreeMap::from([(1, entry(10, "n1", 10)), (2, entry(10, "n1", 20))]);
+        let b = BTreeMap::from([(1, entry(11, "n2", 11))]);
+        let c = BTreeMap::from([(2, entry(9, "n3", 29)), (3, entry(1, "n3", 30))]);
+        assert_merge_laws::<LwwMap>(&a, &b, &c);
+        let value = joined::<LwwMap>(&[&a, &b, &c]).value();
+        assert_eq!(value, BTreeMap::from([(1, 11), (2, 20), (3, 30)]));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::causal_context::{join_dots, CausalContext, Dot};
+use crate::crdt::{Crdt, CrdtWorkload};
+use crate::node::{MsgType, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MvEntry {
+    pub dot: Dot,
+    pub value: MsgType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct MvRegisterState {
+    pub entries: Vec<MvEntry>,
+    pub context: CausalContext,
+}
+
+impl MvRegisterState {
+    fn dots(&self) -> BTreeSet<Dot> {
+        self.entries.iter().map(|entry| entry.dot.to_owned()).collect()
+    }
+
+    fn join(&mut self, other: MvRegisterState) {
+        let kept = join_dots(&self.dots(), &self.context, &other.dots(), &other.context);
+        let mut entries = std::mem::take(&mut self.entries);
+        entries.extend(other.entries);
+        entries.retain(|entry| kept.contains(&entry.dot));
+        entries.sort_by(|a, b| a.dot.cmp(&b.dot));
+        entries.dedup_by(|a, b| a.dot == b.dot);
+        self.entries = entries;
+        self.context.merge(&other.context);
+    }
+}
+
+// Multi-value register: every write gets a dot and replaces the values whose dots it has
+// seen. Concurrent writes have not seen each other's dots, so all of them survive until a
+// write that has seen them replaces them together.
+#[derive(Default)]
+pub struct MvRegister {
+    state: MvRegisterState,
+    delta: Option<MvRegisterState>,
+}
+
+impl MvRegister {
+    pub fn write(&mut self, node_id: &NodeId, value: MsgType) {
+        let dot = self.state.context.next_dot(node_id);
+        let mut delta = MvRegisterState::default();
+        for replaced in self.state.dots() {
+            delta.context.insert(replaced);
+        }
+        delta.context.insert(dot.to_owned());
+        delta.entries = vec![MvEntry { dot, value }];
+        self.state.entries = delta.entries.to_owned();
+        self.record_delta(delta);
+    }
+
+    // Drops every value written so far, like a write that leaves nothing behind.
+    pub fn clear(&mut self) {
+        let mut delta = MvRegisterState::default();
+        for replaced in self.state.dots() {
+            delta.context.insert(replaced);
+        }
+        self.state.entries.clear();
+        self.record_delta(delta);
+    }
+
+    fn record_delta(&mut self, delta: MvRegisterState) {
+        match self.delta.as_mut() {
+            Some(pending) => pending.join(delta),
+            None => self.delta = Some(delta),
+        }
+    }
+}
+
+impl Crdt for MvRegister {
+    type State = MvRegisterState;
+    type Value = Vec<MsgType>;
+
+    fn state(&self) -> Self::State {
+        self.state.to_owned()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        self.state.join(state);
+    }
+
+    fn value(&self) -> Self::Value {
+        let mut values: Vec<MsgType> = self.state.entries.iter().map(|entry| entry.value).collect();
+        values.sort();
+        values.dedup();
+        values
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        self.delta.take()
+    }
+}
+
+impl CrdtWorkload for MvRegister {
+    type Request = MvRegisterRequest;
+    type Response = MvRegisterResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: MvRegisterRequest) -> MvRegisterResponse {
+        match request {
+            MvRegisterRequest::Write { value } => {
+                self.write(node_id, value);
+                MvRegisterResponse::WriteOk
+            }
+            MvRegisterRequest::Read => MvRegisterResponse::ReadOk {
+                value: self.value(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MvRegisterRequest {
+    Write { value: MsgType },
+    Read,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MvRegisterResponse {
+    WriteOk,
+    ReadOk { value: Vec<MsgType> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+
+    fn node(id: &str) -> NodeId {
+        id.to_string()
+    }
+
+    #[test]
+    fn later_write_replaces_what_it_saw() {
+        let mut a = MvRegister::default();
+        a.write(&node("n1"), 1);
+        let mut b = joined::<MvRegister>(&[&a.state()]);
+        b.write(&node("n2"), 2);
+        let mut c = MvRegister::default();
+        c.write(&node("n3"), 3);
+        let (a, b, c) = (a.state(), b.state(), c.state());
+        assert_merge_laws::<MvRegister>(&a, &b, &c);
+        // b overwrote a, c is concurrent with both
+        assert_eq!(joined::<MvRegister>(&[&a, &b, &c]).value(), vec![2, 3]);
+    }
+
+    #[test]
+    fn concurrent_writes_survive_until_a_later_write() {
+        let mut a = MvRegister::default();
+        a.write(&node("n1"), 1);
+        let mut b = MvRegister::default();
+        b.write(&node("n2"), 2);
+        a.merge(b.state());
+        b.merge(a.state());
+        assert_eq!(a.value(), vec![1, 2]);
+        assert_eq!(b.value(), vec![1, 2]);
+        b.write(&node("n2"), 3);
+        a.merge(b.state());
+        assert_eq!(a.value(), vec![3]);
+    }
+}
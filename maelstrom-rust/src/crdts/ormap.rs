@@ -0,0 +1,611 @@
+use crate::causal_context::{join_dots, CausalContext, Dot};
+use crate::crdt::{Crdt, CrdtWorkload, KEY_DOES_NOT_EXIST, MALFORMED_REQUEST};
+use crate::crdts::mvregister::{MvRegister, MvRegisterState};
+use crate::crdts::orset::{OrSet, OrSetState};
+use crate::crdts::pncounter::{PnCounter, PnCounterState};
+use crate::node::{MsgType, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// A counter that can be cleared: `reset` holds the counts seen by every clear, merged the
+// same way as the counts themselves, and the value is what was counted past them.
+#[derive(Default)]
+pub struct OrMapCounter {
+    counter: PnCounter,
+    reset: PnCounter,
+    cleared: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct OrMapCounterState {
+    pub counter: PnCounterState,
+    pub reset: PnCounterState,
+}
+
+impl OrMapCounter {
+    fn add(&mut self, node_id: &NodeId, delta: i64) {
+        self.counter.add(node_id, delta);
+    }
+
+    fn clear(&mut self) {
+        self.reset.merge(self.counter.state());
+        self.cleared = true;
+    }
+}
+
+impl Crdt for OrMapCounter {
+    type State = OrMapCounterState;
+    type Value = i64;
+
+    fn state(&self) -> Self::State {
+        OrMapCounterState {
+            counter: self.counter.state(),
+            reset: self.reset.state(),
+        }
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        self.counter.merge(state.counter);
+        self.reset.merge(state.reset);
+    }
+
+    fn value(&self) -> Self::Value {
+        self.counter.value() - self.reset.value()
+    }
+
+    // A clear ships the whole state, so no replica learns a reset without the counts under it.
+    fn take_delta(&mut self) -> Option<Self::State> {
+        let counter = self.counter.take_delta();
+        if std::mem::take(&mut self.cleared) {
+            return Some(self.state());
+        }
+        counter.map(|counter| OrMapCounterState {
+            counter,
+            reset: PnCounterState::default(),
+        })
+    }
+}
+
+// The CRDTs a map can hold, nested maps included.
+pub enum OrMapValue {
+    Counter(OrMapCounter),
+    Set(OrSet),
+    Register(MvRegister),
+    Map(OrMap),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", content = "state", rename_all = "snake_case")]
+pub enum OrMapValueState {
+    Counter(OrMapCounterState),
+    Set(OrSetState),
+    Register(MvRegisterState),
+    Map(OrMapState),
+}
+
+// Kinds of values; a key holds at most one value of each.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OrMapKind {
+    Counter,
+    Set,
+    Register,
+    Map,
+}
+
+// A mutation addressed to the value at the end of a path.
+enum OrMapOp {
+    Increment(i64),
+    Add(MsgType),
+    Remove(MsgType),
+    Write(MsgType),
+    RemoveKey,
+}
+
+impl OrMapOp {
+    // The kind of value the op applies to, a map when it goes further down the path.
+    fn kind(&self, nested: bool) -> Result<OrMapKind, u32> {
+        match (self, nested) {
+            (_, true) => Ok(OrMapKind::Map),
+            (OrMapOp::Increment(_), false) => Ok(OrMapKind::Counter),
+            (OrMapOp::Add(_) | OrMapOp::Remove(_), false) => Ok(OrMapKind::Set),
+            (OrMapOp::Write(_), false) => Ok(OrMapKind::Register),
+            (OrMapOp::RemoveKey, false) => Err(KEY_DOES_NOT_EXIST),
+        }
+    }
+}
+
+impl OrMapValueState {
+    fn kind(&self) -> OrMapKind {
+        match self {
+            OrMapValueState::Counter(_) => OrMapKind::Counter,
+            OrMapValueState::Set(_) => OrMapKind::Set,
+            OrMapValueState::Register(_) => OrMapKind::Register,
+            OrMapValueState::Map(_) => OrMapKind::Map,
+        }
+    }
+}
+
+impl OrMapValue {
+    fn of_kind(kind: OrMapKind) -> Self {
+        match kind {
+            OrMapKind::Counter => OrMapValue::Counter(OrMapCounter::default()),
+            OrMapKind::Set => OrMapValue::Set(OrSet::default()),
+            OrMapKind::Register => OrMapValue::Register(MvRegister::default()),
+            OrMapKind::Map => OrMapValue::Map(OrMap::default()),
+        }
+    }
+
+    fn from_state(state: OrMapValueState) -> Self {
+        fn merged<C: Crdt>(state: C::State) -> C {
+            let mut crdt = C::default();
+            crdt.merge(state);
+            crdt
+        }
+        match state {
+            OrMapValueState::Counter(state) => OrMapValue::Counter(merged(state)),
+            OrMapValueState::Set(state) => OrMapValue::Set(merged(state)),
+            OrMapValueState::Register(state) => OrMapValue::Register(merged(state)),
+            OrMapValueState::Map(state) => OrMapValue::Map(merged(state)),
+        }
+    }
+
+    fn state(&self) -> OrMapValueState {
+        match self {
+            OrMapValue::Counter(counter) => OrMapValueState::Counter(counter.state()),
+            OrMapValue::Set(set) => OrMapValueState::Set(set.state()),
+            OrMapValue::Register(register) => OrMapValueState::Register(register.state()),
+            OrMapValue::Map(map) => OrMapValueState::Map(map.state()),
+        }
+    }
+
+    fn merge(&mut self, state: OrMapValueState) {
+        match (self, state) {
+            (OrMapValue::Counter(ours), OrMapValueState::Counter(theirs)) => ours.merge(theirs),
+            (OrMapValue::Set(ours), OrMapValueState::Set(theirs)) => ours.merge(theirs),
+            (OrMapValue::Register(ours), OrMapValueState::Register(theirs)) => ours.merge(theirs),
+            (OrMapValue::Map(ours), OrMapValueState::Map(theirs)) => ours.merge(theirs),
+            _ => unreachable!("values only merge with states of their own kind"),
+        }
+    }
+
+    // The delta of the last mutation, or an empty state of the same kind if it changed nothing.
+    fn take_delta(&mut self) -> OrMapValueState {
+        let delta = match self {
+            OrMapValue::Counter(counter) => counter.take_delta().map(OrMapValueState::Counter),
+            OrMapValue::Set(set) => set.take_delta().map(OrMapValueState::Set),
+            OrMapValue::Register(register) => register.take_delta().map(OrMapValueState::Register),
+            OrMapValue::Map(map) => map.take_delta().map(OrMapValueState::Map),
+        };
+        delta.unwrap_or_else(|| match self {
+            OrMapValue::Counter(_) => OrMapValueState::Counter(OrMapCounterState::default()),
+            OrMapValue::Set(_) => OrMapValueState::Set(OrSetState::default()),
+            OrMapValue::Register(_) => OrMapValueState::Register(MvRegisterState::default()),
+            OrMapValue::Map(_) => OrMapValueState::Map(OrMapState::default()),
+        })
+    }
+
+    fn apply(&mut self, node_id: &NodeId, op: &OrMapOp) -> Result<(), u32> {
+        match (self, op) {
+            (OrMapValue::Counter(counter), OrMapOp::Increment(delta)) => {
+                counter.add(node_id, *delta)
+            }
+            (OrMapValue::Set(set), OrMapOp::Add(element)) => set.add(node_id, *element),
+            (OrMapValue::Set(set), OrMapOp::Remove(element)) => set.remove(element),
+            (OrMapValue::Register(register), OrMapOp::Write(value)) => {
+                register.write(node_id, *value)
+            }
+            _ => return Err(MALFORMED_REQUEST),
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        match self {
+            OrMapValue::Counter(counter) => counter.clear(),
+            OrMapValue::Set(set) => set.clear(),
+            OrMapValue::Register(register) => register.clear(),
+            OrMapValue::Map(map) => map.clear(),
+        }
+    }
+
+    fn value(&self) -> serde_json::Value {
+        match self {
+            OrMapValue::Counter(counter) => serde_json::json!(counter.value()),
+            OrMapValue::Set(set) => serde_json::json!(set.value()),
+            OrMapValue::Register(register) => serde_json::json!(register.value()),
+            OrMapValue::Map(map) => map.value(),
+        }
+    }
+}
+
+// The value of one kind under a key. A removed one keeps its entry with no dots and its
+// value cleared.
+pub struct OrMapEntry {
+    dots: BTreeSet<Dot>,
+    value: OrMapValue,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OrMapEntryState {
+    pub dots: BTreeSet<Dot>,
+    pub value: OrMapValueState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct OrMapState {
+    pub entries: BTreeMap<String, Vec<OrMapEntryState>>,
+    pub context: CausalContext,
+}
+
+// Observed-remove map of CRDTs. Keys are kept alive by dots like OR-Set elements: every update
+// under a key replaces the key's observed dots with a fresh one, and removing a key drops the
+// dots it observed, so an update concurrent with a removal keeps the key. Values merge as
+// their own CRDT. Removing a key also clears its value, which stays behind hidden: a key
+// brought back, concurrently or later, holds only what the removal had not seen, and its
+// value goes on from the old dots and counts instead of reusing them.
+//
+// Each kind of value under a key has its own entry and dots, so a key brought back as another
+// kind is not undone by a replica that missed the removal. Kinds created concurrently both
+// live on, and reads show the last one in `OrMapKind` order.
+#[derive(Default)]
+pub struct OrMap {
+    entries: BTreeMap<String, BTreeMap<OrMapKind, OrMapEntry>>,
+    context: CausalContext,
+    delta: Option<Box<OrMap>>,
+}
+
+impl OrMap {
+    // The kind of value a read shows under `key`, if the key is live.
+    fn live_kind(&self, key: &str) -> Option<OrMapKind> {
+        let kinds = self.entries.get(key)?;
+        kinds
+            .iter()
+            .filter(|(_, entry)| !entry.dots.is_empty())
+            .map(|(kind, _)| *kind)
+            .next_back()
+    }
+
+    fn update(&mut self, node_id: &NodeId, path: &[String], op: &OrMapOp) -> Result<(), u32> {
+        let (key, rest) = path.split_first().ok_or(MALFORMED_REQUEST)?;
+        let live_kind = self.live_kind(key);
+        if rest.is_empty() && matches!(op, OrMapOp::RemoveKey) {
+            if live_kind.is_none() {
+                return Err(KEY_DOES_NOT_EXIST);
+            }
+            self.remove_key(key);
+            return Ok(());
+        }
+        let kind = op.kind(!rest.is_empty())?;
+        if live_kind.is_some_and(|live_kind| live_kind != kind) {
+            return Err(MALFORMED_REQUEST);
+        }
+        // a removed key is brought back on the cleared value of its kind
+        let kinds = self.entries.entry(key.to_owned()).or_default();
+        let created = !kinds.contains_key(&kind);
+        let entry = kinds.entry(kind).or_insert_with(|| OrMapEntry {
+            dots: BTreeSet::new(),
+            value: OrMapValue::of_kind(kind),
+        });
+        let applied = match (&mut entry.value, rest.is_empty()) {
+            (OrMapValue::Map(map), false) => map.update(node_id, rest, op),
+            (value, true) => value.apply(node_id, op),
+            _ => Err(MALFORMED_REQUEST),
+        };
+        if let Err(code) = applied {
+            if created {
+                kinds.remove(&kind);
+                if kinds.is_empty() {
+                    self.entries.remove(key);
+                }
+            }
+            return Err(code);
+        }
+        let dot = self.context.next_dot(node_id);
+        let mut delta = OrMapState::default();
+        delta.context.insert(dot.to_owned());
+        let mut entry_deltas = Vec::new();
+        for (entry_kind, entry) in kinds.iter_mut() {
+            if *entry_kind == kind {
+                let observed = std::mem::replace(&mut entry.dots, BTreeSet::from([dot.to_owned()]));
+                for observed in observed {
+                    delta.context.insert(observed);
+                }
+                entry_deltas.push(OrMapEntryState {
+                    dots: BTreeSet::from([dot.to_owned()]),
+                    value: entry.value.take_delta(),
+                });
+            } else if !entry.dots.is_empty() {
+                // a kind created concurrently goes, as the update saw it
+                entry_deltas.push(Self::remove_entry(entry, &mut delta.context));
+            }
+        }
+        delta.entries.insert(key.to_owned(), entry_deltas);
+        self.record_delta(delta);
+        Ok(())
+    }
+
+    // The delta carries the cleared values, so other replicas drop what this one had seen of them.
+    fn remove_key(&mut self, key: &str) {
+        let Some(kinds) = self.entries.get_mut(key) else {
+            return;
+        };
+        let mut delta = OrMapState::default();
+        let entry_deltas = kinds
+            .values_mut()
+            .filter(|entry| !entry.dots.is_empty())
+            .map(|entry| Self::remove_entry(entry, &mut delta.context))
+            .collect();
+        delta.entries.insert(key.to_owned(), entry_deltas);
+        self.record_delta(delta);
+    }
+
+    fn remove_entry(entry: &mut OrMapEntry, context: &mut CausalContext) -> OrMapEntryState {
+        entry.value.clear();
+        for dot in std::mem::take(&mut entry.dots) {
+            context.insert(dot);
+        }
+        OrMapEntryState {
+            dots: BTreeSet::new(),
+            value: entry.value.take_delta(),
+        }
+    }
+
+    fn clear(&mut self) {
+        let live: Vec<String> = self
+            .entries
+            .keys()
+            .filter(|key| self.live_kind(key).is_some())
+            .cloned()
+            .collect();
+        for key in live {
+            self.remove_key(&key);
+        }
+    }
+
+    fn record_delta(&mut self, delta: OrMapState) {
+        self.delta.get_or_insert_with(Default::default).merge(delta);
+    }
+}
+
+impl Crdt for OrMap {
+    type State = OrMapState;
+    type Value = serde_json::Value;
+
+    fn state(&self) -> Self::State {
+        let entries = self
+            .entries
+            .iter()
+            .map(|(key, kinds)| {
+                let states = kinds
+                    .values()
+                    .map(|entry| OrMapEntryState {
+                        dots: entry.dots.to_owned(),
+                        value: entry.value.state(),
+                    })
+                    .collect();
+                (key.to_owned(), states)
+            })
+            .collect();
+        OrMapState {
+            entries,
+            context: self.context.to_owned(),
+        }
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        let none = BTreeSet::new();
+        let mut theirs_entries: BTreeMap<(String, OrMapKind), OrMapEntryState> = state
+            .entries
+            .into_iter()
+            .flat_map(|(key, entries)| {
+                entries
+                    .into_iter()
+                    .map(move |entry| ((key.to_owned(), entry.value.kind()), entry))
+            })
+            .collect();
+        for (key, kinds) in self.entries.iter_mut() {
+            for (kind, ours) in kinds.iter_mut() {
+                let theirs = theirs_entries.remove(&(key.to_owned(), *kind));
+                ours.dots = join_dots(
+                    &ours.dots,
+                    &self.context,
+                    theirs.as_ref().map_or(&none, |entry| &entry.dots),
+                    &state.context,
+                );
+                if let Some(theirs) = theirs {
+                    ours.value.merge(theirs.value);
+                }
+            }
+        }
+        for ((key, kind), theirs) in theirs_entries {
+            let entry = OrMapEntry {
+                dots: join_dots(&none, &self.context, &theirs.dots, &state.context),
+                value: OrMapValue::from_state(theirs.value),
+            };
+            self.entries.entry(key).or_default().insert(kind, entry);
+        }
+        self.context.merge(&state.context);
+    }
+
+    fn value(&self) -> Self::Value {
+        let object = self
+            .entries
+            .iter()
+            .filter_map(|(key, kinds)| {
+                let kind = self.live_kind(key)?;
+                Some((key.to_owned(), kinds[&kind].value.value()))
+            })
+            .collect();
+        serde_json::Value::Object(object)
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        self.delta.take().map(|delta| delta.state())
+    }
+}
+
+impl CrdtWorkload for OrMap {
+    type Request = OrMapRequest;
+    type Response = OrMapResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: OrMapRequest) -> OrMapResponse {
+        let (path, op, done) = match request {
+            OrMapRequest::Increment { path, delta } => {
+                (path, OrMapOp::Increment(delta), OrMapResponse::IncrementOk)
+            }
+            OrMapRequest::Add { path, element } => {
+                (path, OrMapOp::Add(element), OrMapResponse::AddOk)
+            }
+            OrMapRequest::Remove { path, element } => {
+                (path, OrMapOp::Remove(element), OrMapResponse::RemoveOk)
+            }
+            OrMapRequest::Write { path, value } => {
+                (path, OrMapOp::Write(value), OrMapResponse::WriteOk)
+            }
+            OrMapRequest::RemoveKey { path } => {
+                (path, OrMapOp::RemoveKey, OrMapResponse::RemoveKeyOk)
+            }
+            OrMapRequest::Read => {
+                return OrMapResponse::ReadOk {
+                    value: self.value(),
+                }
+            }
+        };
+        match self.update(node_id, &path, &op) {
+            Ok(()) => done,
+            Err(code) => OrMapResponse::Error {
+                code,
+                text: format!("cannot apply to {}", path.join("/")),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrMapRequest {
+    Increment { path: Vec<String>, delta: i64 },
+    Add { path: Vec<String>, element: MsgType },
+    Remove { path: Vec<String>, element: MsgType },
+    Write { path: Vec<String>, value: MsgType },
+    RemoveKey { path: Vec<String> },
+    Read,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrMapResponse {
+    IncrementOk,
+    AddOk,
+    RemoveOk,
+    WriteOk,
+    RemoveKeyOk,
+    ReadOk { value: serde_json::Value },
+    Error { code: u32, text: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+    use serde_json::json;
+
+    fn node(id: &str) -> NodeId {
+        id.to_string()
+    }
+
+    fn path(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_laws() {
+        let mut a = OrMap::default();
+        a.update(&node("n1"), &path(&["c"]), &OrMapOp::Increment(2)).unwrap();
+        a.update(&node("n1"), &path(&["s"]), &OrMapOp::Add(1)).unwrap();
+        let mut b = joined::<OrMap>(&[&a.state()]);
+        b.update(&node("n2"), &path(&["s"]), &OrMapOp::RemoveKey).unwrap();
+        b.update(&node("n2"), &path(&["m", "r"]), &OrMapOp::Write(7)).unwrap();
+        let mut c = OrMap::default();
+        c.update(&node("n3"), &path(&["c"]), &OrMapOp::Increment(-1)).unwrap();
+        c.update(&node("n3"), &path(&["s"]), &OrMapOp::Add(3)).unwrap();
+        let (a, b, c) = (a.state(), b.state(), c.state());
+        assert_merge_laws::<OrMap>(&a, &b, &c);
+        let value = joined::<OrMap>(&[&a, &b, &c]).value();
+        assert_eq!(value, json!({"c": 1, "m": {"r": [7]}, "s": [3]}));
+    }
+
+    #[test]
+    fn recreated_key_survives_merge_with_the_old_value() {
+        let mut old = OrMap::default();
+        old.update(&node("n1"), &path(&["s"]), &OrMapOp::Add(5)).unwrap();
+        old.update(&node("n1"), &path(&["c"]), &OrMapOp::Increment(5)).unwrap();
+        old.update(&node("n1"), &path(&["r"]), &OrMapOp::Write(5)).unwrap();
+        let mut map = joined::<OrMap>(&[&old.state()]);
+        for key in ["s", "c", "r"] {
+            map.update(&node("n1"), &path(&[key]), &OrMapOp::RemoveKey).unwrap();
+        }
+        assert_eq!(map.value(), json!({}));
+        map.update(&node("n1"), &path(&["s"]), &OrMapOp::Add(7)).unwrap();
+        map.update(&node("n1"), &path(&["c"]), &OrMapOp::Increment(1)).unwrap();
+        map.update(&node("n1"), &path(&["r"]), &OrMapOp::Write(7)).unwrap();
+        let expected = json!({"c": 1, "r": [7], "s": [7]});
+        assert_eq!(map.value(), expected);
+        // replicas still holding the old values neither drop nor dominate the new ones
+        assert_eq!(joined::<OrMap>(&[&old.state(), &map.state()]).value(), expected);
+        old.merge(map.take_delta().unwrap());
+        assert_eq!(old.value(), expected);
+    }
+
+    #[test]
+    fn update_concurrent_with_removal_keeps_only_what_it_added() {
+        let mut a = OrMap::default();
+        a.update(&node("n1"), &path(&["s"]), &OrMapOp::Add(5)).unwrap();
+        a.update(&node("n1"), &path(&["c"]), &OrMapOp::Increment(5)).unwrap();
+        let mut b = joined::<OrMap>(&[&a.state()]);
+        a.update(&node("n1"), &path(&["s"]), &OrMapOp::RemoveKey).unwrap();
+        a.update(&node("n1"), &path(&["c"]), &OrMapOp::RemoveKey).unwrap();
+        b.update(&node("n2"), &path(&["s"]), &OrMapOp::Add(6)).unwrap();
+        b.update(&node("n2"), &path(&["c"]), &OrMapOp::Increment(1)).unwrap();
+        let value = joined::<OrMap>(&[&a.state(), &b.state()]).value();
+        assert_eq!(value, json!({"c": 1, "s": [6]}));
+    }
+
+    #[test]
+    fn key_brought_back_as_another_kind_outlives_a_stale_replica() {
+        let mut a = OrMap::default();
+        a.update(&node("n1"), &path(&["k"]), &OrMapOp::Add(1)).unwrap();
+        let mut stale = joined::<OrMap>(&[&a.state()]);
+        a.update(&node("n1"), &path(&["k"]), &OrMapOp::RemoveKey).unwrap();
+        assert_eq!(
+            a.update(&node("n1"), &path(&["k"]), &OrMapOp::RemoveKey),
+            Err(KEY_DOES_NOT_EXIST)
+        );
+        a.update(&node("n1"), &path(&["k"]), &OrMapOp::Increment(3)).unwrap();
+        assert_eq!(
+            a.update(&node("n1"), &path(&["k"]), &OrMapOp::Add(1)),
+            Err(MALFORMED_REQUEST)
+        );
+        let fresh = a.state();
+        a.merge(stale.state());
+        assert_eq!(a.value(), json!({"k": 3}));
+        stale.merge(fresh);
+        assert_eq!(stale.value(), json!({"k": 3}));
+    }
+
+    #[test]
+    fn kinds_created_concurrently_both_survive_merge() {
+        let mut a = OrMap::default();
+        a.update(&node("n1"), &path(&["k"]), &OrMapOp::Add(1)).unwrap();
+        let mut b = OrMap::default();
+        b.update(&node("n2"), &path(&["k"]), &OrMapOp::Increment(2)).unwrap();
+        let mut map = joined::<OrMap>(&[&a.state(), &b.state()]);
+        assert_eq!(map.value(), json!({"k": [1]}));
+        assert_merge_laws::<OrMap>(&a.state(), &b.state(), &map.state());
+        // an update made after seeing both drops the other kind, on every replica
+        map.update(&node("n1"), &path(&["k"]), &OrMapOp::Add(2)).unwrap();
+        b.merge(map.state());
+        assert_eq!(b.value(), json!({"k": [1, 2]}));
+        assert!(b.entries["k"][&OrMapKind::Counter].dots.is_empty());
+    }
+}
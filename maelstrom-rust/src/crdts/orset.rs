@@ -0,0 +1,196 @@
+use crate::causal_context::{join_dots, CausalContext, Dot};
+use crate::crdt::{Crdt, CrdtWorkload};
+use crate::node::{MsgType, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Observed-remove set without tombstones: every add is tagged with a fresh dot and the causal
+// context remembers all dots ever seen, so a dot missing from a replica that has seen it was
+// removed there. An add concurrent with a remove carries a dot the remove never saw, so the
+// element survives the merge: add wins.
+#[derive(Default)]
+pub struct OrSet {
+    state: OrSetState,
+    delta: Option<OrSetState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct OrSetState {
+    pub entries: BTreeMap<MsgType, BTreeSet<Dot>>,
+    pub context: CausalContext,
+}
+
+impl OrSetState {
+    fn join(&mut self, other: OrSetState) {
+        let elements: BTreeSet<MsgType> = self
+            .entries
+            .keys()
+            .chain(other.entries.keys())
+            .copied()
+            .collect();
+        let none = BTreeSet::new();
+        for element in elements {
+            let dots = join_dots(
+                self.entries.get(&element).unwrap_or(&none),
+                &self.context,
+                other.entries.get(&element).unwrap_or(&none),
+                &other.context,
+            );
+            if dots.is_empty() {
+                self.entries.remove(&element);
+            } else {
+                self.entries.insert(element, dots);
+            }
+        }
+        self.context.merge(&other.context);
+    }
+}
+
+impl OrSet {
+    // The new dot replaces the ones observed so far; the delta's context carries those too,
+    // so replicas drop them.
+    pub fn add(&mut self, node_id: &NodeId, element: MsgType) {
+        let dot = self.state.context.next_dot(node_id);
+        let mut delta = OrSetState::default();
+        delta.context.insert(dot.to_owned());
+        let observed = self.state.entries.insert(element, BTreeSet::from([dot.to_owned()]));
+        for observed in observed.into_iter().flatten() {
+            delta.context.insert(observed);
+        }
+        delta.entries.insert(element, BTreeSet::from([dot]));
+        self.record_delta(delta);
+    }
+
+    pub fn remove(&mut self, element: &MsgType) {
+        if let Some(dots) = self.state.entries.remove(element) {
+            let mut delta = OrSetState::default();
+            for dot in dots {
+                delta.context.insert(dot);
+            }
+            self.record_delta(delta);
+        }
+    }
+
+    // Removes every element observed so far; concurrent adds still survive the merge.
+    pub fn clear(&mut self) {
+        let mut delta = OrSetState::default();
+        for dot in std::mem::take(&mut self.state.entries).into_values().flatten() {
+            delta.context.insert(dot);
+        }
+        self.record_delta(delta);
+    }
+
+    fn record_delta(&mut self, delta: OrSetState) {
+        match self.delta.as_mut() {
+            Some(pending) => pending.join(delta),
+            None => self.delta = Some(delta),
+        }
+    }
+}
+
+impl Crdt for OrSet {
+    type State = OrSetState;
+    type Value = Vec<MsgType>;
+
+    fn state(&self) -> Self::State {
+        self.state.to_owned()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        self.state.join(state);
+    }
+
+    fn value(&self) -> Self::Value {
+        self.state.entries.keys().cloned().collect()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        self.delta.take()
+    }
+}
+
+impl CrdtWorkload for OrSet {
+    type Request = OrSetRequest;
+    type Response = OrSetResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: OrSetRequest) -> OrSetResponse {
+        match request {
+            OrSetRequest::Add { element } => {
+                self.add(node_id, element);
+                OrSetResponse::AddOk
+            }
+            OrSetRequest::Remove { element } => {
+                self.remove(&element);
+                OrSetResponse::RemoveOk
+            }
+            OrSetRequest::Read => OrSetResponse::ReadOk {
+                value: self.value(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrSetRequest {
+    Add { element: MsgType },
+    Remove { element: MsgType },
+    Read,
+}
+
+// named after the maelstrom reply types, like the other CRDTs' responses
+#[allow(clippy::enum_variant_names)]
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrSetResponse {
+    AddOk,
+    RemoveOk,
+    ReadOk { value: Vec<MsgType> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+
+    fn node(id: &str) -> NodeId {
+        id.to_string()
+    }
+
+    #[test]
+    fn concurrent_add_wins_over_remove() {
+        let mut a = OrSet::default();
+        a.add(&node("n1"), 1);
+        a.add(&node("n1"), 2);
+        let mut b = joined::<OrSet>(&[&a.state()]);
+        b.remove(&1);
+        b.add(&node("n2"), 3);
+        let mut c = OrSet::default();
+        c.add(&node("n3"), 1);
+        let (a, b, c) = (a.state(), b.state(), c.state());
+        assert_merge_laws::<OrSet>(&a, &b, &c);
+        assert_eq!(joined::<OrSet>(&[&a, &b]).value(), vec![2, 3]);
+        // the add at n3 never saw the remove, so it wins
+        assert_eq!(joined::<OrSet>(&[&a, &b, &c]).value(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn removal_spreads_through_merge() {
+        let mut a = OrSet::default();
+        a.add(&node("n1"), 1);
+        let mut b = joined::<OrSet>(&[&a.state()]);
+        b.remove(&1);
+        a.merge(b.state());
+        assert!(a.value().is_empty());
+    }
+
+    #[test]
+    fn remove_delta_drops_the_element_elsewhere() {
+        let mut a = OrSet::default();
+        a.add(&node("n1"), 1);
+        let mut b = joined::<OrSet>(&[&a.take_delta().unwrap()]);
+        b.remove(&1);
+        a.merge(b.take_delta().unwrap());
+        assert!(a.value().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::crdt::{Crdt, CrdtWorkload};
+use crate::crdts::gcounter::GCounter;
+use crate::node::NodeId;
+use serde::{Deserialize, Serialize};
+
+// Two grow-only counters, one for increments and one for decrements, each merged on its own.
+#[derive(Default)]
+pub struct PnCounter {
+    increments: GCounter,
+    decrements: GCounter,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PnCounterState {
+    pub p: <GCounter as Crdt>::State,
+    pub n: <GCounter as Crdt>::State,
+}
+
+impl PnCounter {
+    pub fn add(&mut self, node_id: &NodeId, delta: i64) {
+        if delta >= 0 {
+            self.increments.increment(node_id, delta as u64);
+        } else {
+            self.decrements.increment(node_id, delta.unsigned_abs());
+        }
+    }
+}
+
+impl Crdt for PnCounter {
+    type State = PnCounterState;
+    type Value = i64;
+
+    fn state(&self) -> Self::State {
+        PnCounterState {
+            p: self.increments.state(),
+            n: self.decrements.state(),
+        }
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        self.increments.merge(state.p);
+        self.decrements.merge(state.n);
+    }
+
+    fn value(&self) -> Self::Value {
+        self.increments.value() as i64 - self.decrements.value() as i64
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        match (self.increments.take_delta(), self.decrements.take_delta()) {
+            (None, None) => None,
+            (p, n) => Some(PnCounterState {
+                p: p.unwrap_or_default(),
+                n: n.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+impl CrdtWorkload for PnCounter {
+    type Request = PnCounterRequest;
+    type Response = PnCounterResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: PnCounterRequest) -> PnCounterResponse {
+        match request {
+            PnCounterRequest::Add { delta } => {
+                self.add(node_id, delta);
+                PnCounterResponse::AddOk
+            }
+            PnCounterRequest::Read => PnCounterResponse::ReadOk {
+                value: self.value(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PnCounterRequest {
+    Add { delta: i64 },
+    Read,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PnCounterResponse {
+    AddOk,
+    ReadOk { value: i64 },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+
+    #[test]
+    fn decrements_go_below_zero() {
+        let n1 = "n1".to_string();
+        let mut counter = PnCounter::default();
+        counter.add(&n1, -3);
+        assert_eq!(counter.value(), -3);
+        counter.add(&n1, 1);
+        counter.add(&n1, -2);
+        assert_eq!(counter.value(), -4);
+        let mut other = PnCounter::default();
+        other.add(&"n2".to_string(), -5);
+        counter.merge(other.state());
+        assert_eq!(counter.value(), -9);
+    }
+
+    #[test]
+    fn increments_and_decrements_merge_apart() {
+        let mut a = PnCounter::default();
+        a.add(&"n1".to_string(), 3);
+        a.add(&"n1".to_string(), -5);
+        let mut b = joined::<PnCounter>(&[&a.state()]);
+        b.add(&"n1".to_string(), 4);
+        let mut c = PnCounter::default();
+        c.add(&"n3".to_string(), -1);
+        let (a, b, c) = (a.state(), b.state(), c.state());
+        assert_merge_laws::<PnCounter>(&a, &b, &c);
+        assert_eq!(joined::<PnCounter>(&[&a, &b, &c]).value(), 1);
+    }
+}
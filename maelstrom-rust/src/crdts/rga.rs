@@ -0,0 +1,208 @@
+use crate::crdt::{Crdt, CrdtWorkload, MALFORMED_REQUEST};
+use crate::node::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Lamport time first, so an element inserted later sorts after everything its inserter saw;
+// the node id breaks ties between concurrent inserts.
+pub type RgaId = (u64, NodeId);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RgaElement {
+    pub id: RgaId,
+    // the element this one was inserted right after, None for the head of the list
+    pub parent: Option<RgaId>,
+    pub value: serde_json::Value,
+    // deleted elements stay as tombstones, later inserts may still be anchored on them
+    pub deleted: bool,
+}
+
+// Replicated growable array: the elements form a tree under their parents, and the list is
+// its pre-order walk with newer siblings first. An insert therefore lands right after its
+// parent on every replica, and concurrent inserts at one spot come out in the same order.
+#[derive(Default)]
+pub struct Rga {
+    elements: BTreeMap<RgaId, RgaElement>,
+    clock: u64,
+    delta: Vec<RgaElement>,
+}
+
+impl Rga {
+    fn ordered(&self) -> Vec<&RgaElement> {
+        let mut children: BTreeMap<Option<&RgaId>, Vec<&RgaElement>> = BTreeMap::new();
+        for element in self.elements.values() {
+            children.entry(element.parent.as_ref()).or_default().push(element);
+        }
+        // ascending ids on the stack, so the newest sibling is popped first
+        let mut stack = children.remove(&None).unwrap_or_default();
+        let mut ordered = Vec::with_capacity(self.elements.len());
+        while let Some(element) = stack.pop() {
+            ordered.push(element);
+            stack.extend(children.remove(&Some(&element.id)).unwrap_or_default());
+        }
+        ordered
+    }
+
+    fn visible(&self) -> Vec<&RgaElement> {
+        self.ordered()
+            .into_iter()
+            .filter(|element| !element.deleted)
+            .collect()
+    }
+
+    // Inserts so the value ends up at `index` among the visible elements.
+    pub fn insert(
+        &mut self,
+        node_id: &NodeId,
+        index: usize,
+        value: serde_json::Value,
+    ) -> Option<()> {
+        let parent = match index {
+            0 => None,
+            _ => Some(self.visible().get(index - 1)?.id.to_owned()),
+        };
+        self.clock += 1;
+        let element = RgaElement {
+            id: (self.clock, node_id.to_owned()),
+            parent,
+            value,
+            deleted: false,
+        };
+        self.delta.push(element.to_owned());
+        self.elements.insert(element.id.to_owned(), element);
+        Some(())
+    }
+
+    pub fn delete(&mut self, index: usize) -> Option<()> {
+        let id = self.visible().get(index)?.id.to_owned();
+        let element = self.elements.get_mut(&id)?;
+        element.deleted = true;
+        self.delta.push(element.to_owned());
+        Some(())
+    }
+}
+
+impl Crdt for Rga {
+    type State = Vec<RgaElement>;
+    type Value = Vec<serde_json::Value>;
+
+    fn state(&self) -> Self::State {
+        self.elements.values().cloned().collect()
+    }
+
+    fn merge(&mut self, state: Self::State) {
+        for theirs in state {
+            self.clock = self.clock.max(theirs.id.0);
+            self.elements
+                .entry(theirs.id.to_owned())
+                .and_modify(|ours| ours.deleted |= theirs.deleted)
+                .or_insert(theirs);
+        }
+    }
+
+    fn value(&self) -> Self::Value {
+        self.visible()
+            .into_iter()
+            .map(|element| element.value.to_owned())
+            .collect()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        (!self.delta.is_empty()).then(|| std::mem::take(&mut self.delta))
+    }
+}
+
+impl CrdtWorkload for Rga {
+    type Request = RgaRequest;
+    type Response = RgaResponse;
+
+    fn handle(&mut self, node_id: &NodeId, request: RgaRequest) -> RgaResponse {
+        let done = match request {
+            RgaRequest::Insert { index, value } => {
+                self.insert(node_id, index, value).map(|_| RgaResponse::InsertOk)
+            }
+            RgaRequest::Delete { index } => self.delete(index).map(|_| RgaResponse::DeleteOk),
+            RgaRequest::Read => Some(RgaResponse::ReadOk {
+                value: self.value(),
+            }),
+        };
+        done.unwrap_or_else(|| RgaResponse::Error {
+            code: MALFORMED_REQUEST,
+            text: "index out of range".to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RgaRequest {
+    Insert {
+        index: usize,
+        value: serde_json::Value,
+    },
+    Delete {
+        index: usize,
+    },
+    Read,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RgaResponse {
+    InsertOk,
+    DeleteOk,
+    ReadOk { value: Vec<serde_json::Value> },
+    Error { code: u32, text: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::tests::{assert_merge_laws, joined};
+    use serde_json::json;
+
+    fn node(id: &str) -> NodeId {
+        id.to_string()
+    }
+
+    #[test]
+    fn concurrent_inserts_at_one_spot_converge() {
+        let mut base = Rga::default();
+        base.insert(&node("n0"), 0, json!("a")).unwrap();
+        base.insert(&node("n0"), 1, json!("b")).unwrap();
+        let mut a = joined::<Rga>(&[&base.state()]);
+        a.insert(&node("n1"), 1, json!("x")).unwrap();
+        a.insert(&node("n1"), 2, json!("y")).unwrap();
+        let mut b = joined::<Rga>(&[&base.state()]);
+        b.insert(&node("n2"), 1, json!("z")).unwrap();
+        let mut c = joined::<Rga>(&[&base.state()]);
+        c.delete(1).unwrap();
+        let (a, b, c) = (a.state(), b.state(), c.state());
+        assert_merge_laws::<Rga>(&a, &b, &c);
+        // x and y were typed in a row and stay together around the concurrent z
+        let expected = vec![json!("a"), json!("z"), json!("x"), json!("y")];
+        assert_eq!(joined::<Rga>(&[&a, &b, &c]).value(), expected);
+        assert_eq!(joined::<Rga>(&[&c, &b, &a]).value(), expected);
+    }
+
+    #[test]
+    fn inserts_may_anchor_on_deleted_elements() {
+        let mut a = Rga::default();
+        a.insert(&node("n1"), 0, json!(1)).unwrap();
+        let mut b = joined::<Rga>(&[&a.state()]);
+        a.delete(0).unwrap();
+        b.insert(&node("n2"), 1, json!(2)).unwrap();
+        a.merge(b.take_delta().unwrap());
+        b.merge(a.take_delta().unwrap());
+        assert_eq!(a.value(), vec![json!(2)]);
+        assert_eq!(b.value(), vec![json!(2)]);
+    }
+
+    #[test]
+    fn out_of_range_index_is_rejected() {
+        let mut rga = Rga::default();
+        assert!(rga.insert(&node("n1"), 1, json!(1)).is_none());
+        assert!(rga.delete(0).is_none());
+        assert!(rga.take_delta().is_none());
+    }
+}
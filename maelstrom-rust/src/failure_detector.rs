@@ -0,0 +1,187 @@
+use crate::node::NodeId;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Instant;
+
+const WINDOW_SIZE: usize = 100;
+// keeps phi sane while only a few, very regular intervals are known
+const MIN_STD_DEVIATION_MS: f64 = 100.0;
+
+#[derive(Clone, Debug)]
+pub enum PeerEvent {
+    Suspect(NodeId),
+    Recover(NodeId),
+}
+
+struct ArrivalWindow {
+    last_arrival: Instant,
+    intervals_ms: VecDeque<f64>,
+}
+
+impl ArrivalWindow {
+    fn mean_and_std_deviation(&self) -> (f64, f64) {
+        let count = self.intervals_ms.len() as f64;
+        let mean = self.intervals_ms.iter().sum::<f64>() / count;
+        let variance = self
+            .intervals_ms
+            .iter()
+            .map(|interval| (interval - mean) * (interval - mean))
+            .sum::<f64>()
+            / count;
+        (mean, variance.sqrt().max(MIN_STD_DEVIATION_MS))
+    }
+}
+
+// Phi accrual failure detector (Hayashibara et al.): instead of a boolean verdict it reports how
+// unlikely the current silence of a peer is, given the arrival intervals seen so far.
+pub struct PhiAccrualDetector {
+    threshold: f64,
+    windows: HashMap<NodeId, ArrivalWindow>,
+    suspected: HashSet<NodeId>,
+    last_sent: HashMap<NodeId, Instant>,
+    subscribers: Vec<Sender<PeerEvent>>,
+}
+
+impl PhiAccrualDetector {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            windows: HashMap::new(),
+            suspected: HashSet::new(),
+            last_sent: HashMap::new(),
+            subscribers: Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<PeerEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // Any message from a peer counts as a heartbeat.
+    pub fn heartbeat(&mut self, peer: &NodeId, now: Instant) {
+        match self.windows.get_mut(peer) {
+            Some(window) => {
+                let interval = now.duration_since(window.last_arrival).as_secs_f64() * 1000.0;
+                if window.intervals_ms.len() >= WINDOW_SIZE {
+                    window.intervals_ms.pop_front();
+                }
+                window.intervals_ms.push_back(interval);
+                window.last_arrival = now;
+            }
+            None => {
+                self.windows.insert(
+                    peer.to_owned(),
+                    ArrivalWindow {
+                        last_arrival: now,
+                        intervals_ms: VecDeque::new(),
+                    },
+                );
+            }
+        }
+        if self.suspected.remove(peer) {
+            self.emit(PeerEvent::Recover(peer.to_owned()));
+        }
+    }
+
+    pub fn phi(&self, peer: &NodeId, now: Instant) -> f64 {
+        let window = match self.windows.get(peer) {
+            Some(window) if !window.intervals_ms.is_empty() => window,
+            _ => return 0.0,
+        };
+        let elapsed_ms = now.duration_since(window.last_arrival).as_secs_f64() * 1000.0;
+        let (mean, std_deviation) = window.mean_and_std_deviation();
+        // logistic approximation of the normal CDF, as in Akka and Cassandra
+        let y = (elapsed_ms - mean) / std_deviation;
+        let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+        let p_later = if elapsed_ms > mean {
+            e / (1.0 + e)
+        } else {
+            1.0 - 1.0 / (1.0 + e)
+        };
+        -p_later.max(f64::MIN_POSITIVE).log10()
+    }
+
+    pub fn is_suspected(&self, peer: &NodeId) -> bool {
+        self.suspected.contains(peer)
+    }
+
+    // Re-evaluate the given peers and notify subscribers of the ones crossing the threshold.
+    pub fn check(&mut self, peers: &[NodeId], now: Instant) {
+        for peer in peers {
+            if self.suspected.contains(peer) || self.phi(peer, now) < self.threshold {
+                continue;
+            }
+            self.suspected.insert(peer.to_owned());
+            self.emit(PeerEvent::Suspect(peer.to_owned()));
+        }
+    }
+
+    pub fn record_sent(&mut self, peer: &NodeId, now: Instant) {
+        self.last_sent.insert(peer.to_owned(), now);
+    }
+
+    pub fn last_sent(&self, peer: &NodeId) -> Option<Instant> {
+        self.last_sent.get(peer).copied()
+    }
+
+    fn emit(&mut self, event: PeerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.to_owned()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // A detector that heard from `peer` every 100 ms and last did so at the returned instant.
+    fn regular_heartbeats(detector: &mut PhiAccrualDetector, peer: &NodeId) -> Instant {
+        let start = Instant::now();
+        let mut now = start;
+        for i in 0..20 {
+            now = start + Duration::from_millis(100 * i);
+            detector.heartbeat(peer, now);
+        }
+        now
+    }
+
+    #[test]
+    fn phi_grows_with_silence() {
+        let peer = "n1".to_string();
+        let mut detector = PhiAccrualDetector::new(8.0);
+        let last = regular_heartbeats(&mut detector, &peer);
+        let phis: Vec<f64> = [0, 100, 300, 600, 1000]
+            .into_iter()
+            .map(|ms| detector.phi(&peer, last + Duration::from_millis(ms)))
+            .collect();
+        assert!(phis.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", phis);
+        assert!(phis[0] < 1.0);
+        assert!(phis[4] > 8.0);
+    }
+
+    #[test]
+    fn unknown_peers_are_not_suspected() {
+        let detector = PhiAccrualDetector::new(8.0);
+        assert_eq!(detector.phi(&"n1".to_string(), Instant::now()), 0.0);
+    }
+
+    #[test]
+    fn long_silence_suspects_and_a_heartbeat_recovers() {
+        let peer = "n1".to_string();
+        let mut detector = PhiAccrualDetector::new(8.0);
+        let events = detector.subscribe();
+        let last = regular_heartbeats(&mut detector, &peer);
+        detector.check(&[peer.to_owned()], last + Duration::from_millis(150));
+        assert!(!detector.is_suspected(&peer));
+        let silent = last + Duration::from_secs(5);
+        detector.check(&[peer.to_owned()], silent);
+        assert!(detector.is_suspected(&peer));
+        detector.heartbeat(&peer, silent);
+        assert!(!detector.is_suspected(&peer));
+        let events: Vec<PeerEvent> = events.try_iter().collect();
+        assert!(matches!(events.as_slice(), [PeerEvent::Suspect(_), PeerEvent::Recover(_)]));
+    }
+}
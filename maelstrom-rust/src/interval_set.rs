@@ -0,0 +1,230 @@
+use crate::node::MsgType;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+// Set of integers stored as disjoint, non-adjacent inclusive ranges. Broadcast values tend to
+// be dense, so this stays small where a HashSet or a flat array grows with every value.
+// On the wire it is a sorted list of [start, end] pairs.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct IntervalSet {
+    ranges: BTreeMap<MsgType, MsgType>,
+    len: usize,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, value: &MsgType) -> bool {
+        self.ranges
+            .range(..=value)
+            .next_back()
+            .is_some_and(|(_, end)| end >= value)
+    }
+
+    // Returns whether the value was new.
+    pub fn insert(&mut self, value: MsgType) -> bool {
+        let len = self.len;
+        self.insert_range(value, value);
+        len != self.len
+    }
+
+    // An empty range, start after end, changes nothing.
+    pub fn insert_range(&mut self, start: MsgType, end: MsgType) {
+        if start > end {
+            return;
+        }
+        let (mut start, mut end) = (start, end);
+        // ranges are disjoint and sorted, so going down from `end` their ends decrease too
+        let touching: Vec<(MsgType, MsgType)> = self
+            .ranges
+            .range(..=end.saturating_add(1))
+            .rev()
+            .take_while(|(_, range_end)| **range_end >= start.saturating_sub(1))
+            .map(|(range_start, range_end)| (*range_start, *range_end))
+            .collect();
+        for (range_start, range_end) in touching {
+            self.ranges.remove(&range_start);
+            self.len = self.len.saturating_sub(range_len(range_start, range_end));
+            start = start.min(range_start);
+            end = end.max(range_end);
+        }
+        self.ranges.insert(start, end);
+        self.len = self.len.saturating_add(range_len(start, end));
+    }
+
+    pub fn extend(&mut self, other: &IntervalSet) {
+        for (start, end) in other.ranges() {
+            self.insert_range(start, end);
+        }
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = (MsgType, MsgType)> + '_ {
+        self.ranges.iter().map(|(start, end)| (*start, *end))
+    }
+
+    // Every single value: only for consumers that need them one by one, like flat replies.
+    pub fn iter(&self) -> impl Iterator<Item = MsgType> + '_ {
+        self.ranges().flat_map(|(start, end)| start..=end)
+    }
+
+    // Cuts the ranges of `other` out of ours, one range at a time.
+    pub fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut difference = IntervalSet::new();
+        for (start, end) in self.ranges() {
+            let mut overlapping: Vec<(MsgType, MsgType)> = other
+                .ranges
+                .range(..=end)
+                .rev()
+                .take_while(|(_, other_end)| **other_end >= start)
+                .map(|(other_start, other_end)| (*other_start, *other_end))
+                .collect();
+            overlapping.reverse();
+            // first value of [start, end] not looked at yet, None once past `end`
+            let mut from = Some(start);
+            for (other_start, other_end) in overlapping {
+                let Some(current) = from else {
+                    break;
+                };
+                if other_start > current {
+                    difference.insert_range(current, other_start - 1);
+                }
+                from = other_end.checked_add(1).filter(|next| *next <= end);
+            }
+            if let Some(current) = from {
+                difference.insert_range(current, end);
+            }
+        }
+        difference
+    }
+}
+
+// Saturates for the few ranges wider than usize can count.
+fn range_len(start: MsgType, end: MsgType) -> usize {
+    usize::try_from(end.abs_diff(start))
+        .ok()
+        .and_then(|len| len.checked_add(1))
+        .unwrap_or(usize::MAX)
+}
+
+impl FromIterator<MsgType> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = MsgType>>(iter: I) -> Self {
+        let mut set = IntervalSet::new();
+        for value in iter {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+impl Serialize for IntervalSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.ranges().map(|(start, end)| [start, end]))
+    }
+}
+
+impl<'de> Deserialize<'de> for IntervalSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ranges: Vec<[MsgType; 2]> = Vec::deserialize(deserializer)?;
+        let mut set = IntervalSet::new();
+        for [start, end] in ranges {
+            if start > end {
+                return Err(D::Error::custom(format!("empty range [{}, {}]", start, end)));
+            }
+            set.insert_range(start, end);
+        }
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ranges: &[(MsgType, MsgType)]) -> IntervalSet {
+        let mut set = IntervalSet::new();
+        for (start, end) in ranges {
+            set.insert_range(*start, *end);
+        }
+        set
+    }
+
+    fn ranges(set: &IntervalSet) -> Vec<(MsgType, MsgType)> {
+        set.ranges().collect()
+    }
+
+    #[test]
+    fn adjacent_and_overlapping_ranges_merge() {
+        assert_eq!(ranges(&set(&[(1, 3), (4, 6)])), vec![(1, 6)]);
+        assert_eq!(ranges(&set(&[(4, 6), (1, 3)])), vec![(1, 6)]);
+        assert_eq!(ranges(&set(&[(1, 5), (3, 8)])), vec![(1, 8)]);
+        assert_eq!(ranges(&set(&[(1, 2), (8, 9), (3, 7)])), vec![(1, 9)]);
+        assert_eq!(ranges(&set(&[(1, 2), (4, 5)])), vec![(1, 2), (4, 5)]);
+        assert_eq!(set(&[(1, 2), (8, 9), (3, 7)]).len(), 9);
+    }
+
+    #[test]
+    fn insert_reports_new_values() {
+        let mut set = set(&[(1, 3)]);
+        assert!(!set.insert(2));
+        assert!(set.insert(4));
+        assert!(set.insert(0));
+        assert_eq!(ranges(&set), vec![(0, 4)]);
+    }
+
+    #[test]
+    fn difference_splits_at_the_edges() {
+        let ours = set(&[(1, 10)]);
+        assert_eq!(ranges(&ours.difference(&set(&[(1, 1)]))), vec![(2, 10)]);
+        assert_eq!(ranges(&ours.difference(&set(&[(10, 12)]))), vec![(1, 9)]);
+        assert_eq!(
+            ranges(&ours.difference(&set(&[(3, 4), (7, 7)]))),
+            vec![(1, 2), (5, 6), (8, 10)]
+        );
+        assert!(ours.difference(&set(&[(0, 11)])).is_empty());
+        assert_eq!(ours.difference(&IntervalSet::new()), ours);
+    }
+
+    #[test]
+    fn difference_matches_the_value_by_value_one() {
+        let ours = set(&[(0, 20), (30, 40), (45, 45)]);
+        let theirs = set(&[(-5, 2), (10, 12), (19, 31), (40, 50)]);
+        let expected: IntervalSet = ours.iter().filter(|value| !theirs.contains(value)).collect();
+        assert_eq!(ours.difference(&theirs), expected);
+    }
+
+    #[test]
+    fn extreme_values_do_not_overflow() {
+        let mut extremes = set(&[
+            (MsgType::MIN, MsgType::MIN + 1),
+            (MsgType::MAX - 1, MsgType::MAX),
+        ]);
+        assert_eq!(extremes.len(), 4);
+        extremes.insert_range(MsgType::MIN, MsgType::MAX);
+        assert_eq!(ranges(&extremes), vec![(MsgType::MIN, MsgType::MAX)]);
+        assert_eq!(extremes.len(), usize::MAX);
+        let cut = extremes.difference(&set(&[(MsgType::MIN, 0)]));
+        assert_eq!(ranges(&cut), vec![(1, MsgType::MAX)]);
+        let cut = extremes.difference(&set(&[(0, MsgType::MAX)]));
+        assert_eq!(ranges(&cut), vec![(MsgType::MIN, -1)]);
+    }
+
+    #[test]
+    fn empty_ranges_are_ignored_and_rejected_on_the_wire() {
+        assert!(set(&[(5, 1)]).is_empty());
+        assert!(serde_json::from_str::<IntervalSet>("[[5, 1]]").is_err());
+        let parsed: IntervalSet = serde_json::from_str("[[4, 6], [1, 3]]").unwrap();
+        assert_eq!(ranges(&parsed), vec![(1, 6)]);
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), "[[1,6]]");
+    }
+}
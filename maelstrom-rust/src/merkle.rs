@@ -0,0 +1,172 @@
+use crate::interval_set::IntervalSet;
+use crate::node::MsgType;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+// Leaves live on this level, so the tree has 2^MERKLE_DEPTH of them.
+pub const MERKLE_DEPTH: u32 = 8;
+
+// Signed, so hashes survive the JSON round trip through maelstrom.
+pub type MerkleHash = i64;
+
+// Values are spread over the leaves by their hash. A leaf hashes its values in order and a
+// parent hashes its two children, so the root changes with any value. The tree is kept up to
+// date as values come in: only the leaves they land in and the paths above them are rehashed.
+pub struct MerkleTree {
+    levels: Vec<Vec<MerkleHash>>,
+    leaves: Vec<IntervalSet>,
+}
+
+impl Default for MerkleTree {
+    fn default() -> Self {
+        let leaves = vec![IntervalSet::new(); 1 << MERKLE_DEPTH];
+        let mut levels = vec![leaves.iter().map(Self::leaf_hash).collect::<Vec<_>>()];
+        while levels[0].len() > 1 {
+            let parents = levels[0]
+                .chunks(2)
+                .map(|pair| Self::parent_hash(pair[0], pair[1]))
+                .collect();
+            levels.insert(0, parents);
+        }
+        Self { levels, leaves }
+    }
+}
+
+impl MerkleTree {
+    pub fn insert(&mut self, value: MsgType) {
+        self.extend([value].into_iter());
+    }
+
+    pub fn extend(&mut self, values: impl Iterator<Item = MsgType>) {
+        let mut changed = BTreeSet::new();
+        for value in values {
+            let leaf = Self::leaf_of(&value);
+            if self.leaves[leaf].insert(value) {
+                changed.insert(leaf);
+            }
+        }
+        for level in (0..=MERKLE_DEPTH as usize).rev() {
+            for index in changed.iter().copied() {
+                self.levels[level][index] = match level == MERKLE_DEPTH as usize {
+                    true => Self::leaf_hash(&self.leaves[index]),
+                    false => {
+                        let children = &self.levels[level + 1];
+                        Self::parent_hash(children[index * 2], children[index * 2 + 1])
+                    }
+                };
+            }
+            changed = changed.into_iter().map(|index| index / 2).collect();
+        }
+    }
+
+    pub fn hash(&self, level: u32, index: usize) -> MerkleHash {
+        self.levels[level as usize][index]
+    }
+
+    pub fn leaf_values(&self, index: usize) -> &IntervalSet {
+        &self.leaves[index]
+    }
+
+    pub fn leaf_of(value: &MsgType) -> usize {
+        (Self::value_hash(value) as u64 >> (64 - MERKLE_DEPTH)) as usize
+    }
+
+    // DefaultHasher::new() uses fixed keys, so every node hashes the same way.
+    fn value_hash(value: &MsgType) -> MerkleHash {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish() as MerkleHash
+    }
+
+    fn leaf_hash(values: &IntervalSet) -> MerkleHash {
+        let mut hasher = DefaultHasher::new();
+        for value in values.iter() {
+            value.hash(&mut hasher);
+        }
+        hasher.finish() as MerkleHash
+    }
+
+    fn parent_hash(left: MerkleHash, right: MerkleHash) -> MerkleHash {
+        let mut hasher = DefaultHasher::new();
+        (left, right).hash(&mut hasher);
+        hasher.finish() as MerkleHash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(values: impl Iterator<Item = MsgType>) -> MerkleTree {
+        let mut tree = MerkleTree::default();
+        tree.extend(values);
+        tree
+    }
+
+    // The leaves reached by descending only into the subtrees whose hashes differ.
+    fn differing_leaves(ours: &MerkleTree, theirs: &MerkleTree) -> Vec<usize> {
+        let mut nodes = vec![(0, 0)];
+        let mut leaves = Vec::new();
+        while let Some((level, index)) = nodes.pop() {
+            if ours.hash(level, index) == theirs.hash(level, index) {
+                continue;
+            }
+            if level == MERKLE_DEPTH {
+                leaves.push(index);
+            } else {
+                nodes.push((level + 1, index * 2));
+                nodes.push((level + 1, index * 2 + 1));
+            }
+        }
+        leaves.sort();
+        leaves
+    }
+
+    #[test]
+    fn same_values_in_any_order_give_the_same_tree() {
+        let ours = tree(0..100);
+        let theirs = tree((0..100).rev());
+        assert_eq!(ours.hash(0, 0), theirs.hash(0, 0));
+        assert!(differing_leaves(&ours, &theirs).is_empty());
+    }
+
+    #[test]
+    fn diff_finds_exactly_the_leaves_of_missing_values() {
+        let ours = tree(0..100);
+        let theirs = tree((0..100).filter(|v| *v != 17 && *v != 42));
+        let mut expected = vec![MerkleTree::leaf_of(&17), MerkleTree::leaf_of(&42)];
+        expected.sort();
+        expected.dedup();
+        assert_ne!(ours.hash(0, 0), theirs.hash(0, 0));
+        assert_eq!(differing_leaves(&ours, &theirs), expected);
+    }
+
+    #[test]
+    fn parents_hash_their_children() {
+        let tree = tree([3, 5, 8, 13].into_iter());
+        for level in 0..MERKLE_DEPTH {
+            for index in 0..(1 << level) {
+                let left = tree.hash(level + 1, index * 2);
+                let right = tree.hash(level + 1, index * 2 + 1);
+                assert_eq!(tree.hash(level, index), MerkleTree::parent_hash(left, right));
+            }
+        }
+        assert!(tree.leaf_values(MerkleTree::leaf_of(&8)).contains(&8));
+    }
+
+    #[test]
+    fn values_added_one_at_a_time_give_the_tree_built_at_once() {
+        let whole = tree(0..300);
+        let mut incremental = MerkleTree::default();
+        for value in (0..300).rev() {
+            incremental.insert(value);
+            incremental.insert(value);
+        }
+        for level in 0..=MERKLE_DEPTH {
+            for index in 0..(1 << level) {
+                assert_eq!(incremental.hash(level, index), whole.hash(level, index));
+            }
+        }
+    }
+}
@@ -1,7 +1,6 @@
 use proto::MlstComm;
 use proto::{MlstBodyResp, MlstBodyType, MlstReq};
 use serde::Serialize;
-use std::collections::HashSet;
 use std::io::{self, Write};
 use std::sync::Mutex;
 
@@ -29,6 +28,7 @@ pub trait Node {
             } => (id, src, dest, body_req),
         };
         let msg_type: MsgTypeType = body_req["type"].as_str().unwrap().to_string();
+        self.observe_received(&src);
         self.dispatch_request(comm_id, msg_type, src, dest, body_req)
     }
 
@@ -43,6 +43,7 @@ pub trait Node {
     }
 
     fn communicate(&self, dest: NodeId, body: MlstBodyType<impl Serialize>) {
+        self.observe_sent(&dest);
         let msg = MlstComm {
             src: self.get_node_id().lock().unwrap().to_owned().unwrap(),
             dest,
@@ -53,6 +54,16 @@ pub trait Node {
         self.write(&str_msg);
     }
 
+    // Hooks for tracking peer liveness from regular traffic.
+    fn observe_received(&self, _src: &NodeId) {}
+
+    fn observe_sent(&self, _dest: &NodeId) {}
+
+    fn send(&self, dest: NodeId, body: impl Serialize) {
+        let raw_val = serde_json::value::to_raw_value(&body).unwrap();
+        self.communicate(dest, MlstBodyType::<u8>::Comm(raw_val));
+    }
+
     fn read(&self) -> String {
         let mut buffer = String::new();
         io::stdin().read_line(&mut buffer).unwrap();
@@ -87,15 +98,13 @@ pub trait Node {
         let _ = io::stderr().write(&with_newline.into_bytes());
     }
 
-    fn set_neighbor_ids(&self, values: Vec<NodeId>);
+    fn set_node_ids(&self, values: Vec<NodeId>);
 
-    fn get_neighbor_ids(&self) -> &Mutex<Vec<NodeId>>;
-
-    fn store_message(&self, message: MsgType);
+    fn get_node_ids(&self) -> &Mutex<Vec<NodeId>>;
 
-    fn check_message(&self, message: &MsgType) -> bool;
+    fn set_neighbor_ids(&self, values: Vec<NodeId>);
 
-    fn get_messages(&self) -> &Mutex<HashSet<MsgType>>;
+    fn get_neighbor_ids(&self) -> &Mutex<Vec<NodeId>>;
 }
 
 pub mod proto {
@@ -0,0 +1,31 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// Every RandomState is seeded differently, which is all the randomness peer sampling needs.
+pub fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+pub fn random_index(len: usize) -> usize {
+    (random_u64() % len as u64) as usize
+}
+
+pub fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, random_index(i + 1));
+    }
+}
+
+pub fn sample<T: Clone>(items: &[T], k: usize) -> Vec<T> {
+    let mut picked = items.to_vec();
+    shuffle(&mut picked);
+    picked.truncate(k);
+    picked
+}
+
+pub fn choose<T: Clone>(items: &[T]) -> Option<T> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(items[random_index(items.len())].to_owned())
+}
@@ -0,0 +1,236 @@
+use crate::bloom::BloomFilter;
+use crate::interval_set::IntervalSet;
+use crate::async_comm_node::AsyncCommNode;
+use crate::node::{CommId, MsgTypeType, NodeId};
+use crate::rng;
+use proto::{MlstBodyReqBloomDigest, MlstBodyReqBloomValues};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BLOOM_SYNC_INTERVAL: Duration = Duration::from_millis(500);
+const BLOOM_FALSE_POSITIVE_RATE: f64 = 0.01;
+// rounds a stored value stays in the digests
+const BLOOM_WINDOW_ROUNDS: usize = 4;
+// every this many rounds the digest covers all values, so older gaps close too
+const BLOOM_FULL_ROUND_EVERY: u64 = 20;
+
+#[derive(Default)]
+pub struct BloomSyncState {
+    pub last_round: Option<Instant>,
+    pub rounds: u64,
+    // values stored during each of the last rounds, newest first
+    pub recent: VecDeque<IntervalSet>,
+}
+
+impl BloomSyncState {
+    pub fn record(&mut self, values: &IntervalSet) {
+        match self.recent.front_mut() {
+            Some(current) => current.extend(values),
+            None => self.recent.push_front(values.to_owned()),
+        }
+    }
+
+    fn window(&self) -> IntervalSet {
+        let mut window = IntervalSet::new();
+        for values in self.recent.iter() {
+            window.extend(values);
+        }
+        window
+    }
+}
+
+// Digests only cover the values stored in the last few rounds, so they stay small however
+// many values there are: a receiver checks just its own recent values against them. A full
+// round now and then covers everything, for values that went missing long ago.
+pub trait MlstBloomSync: AsyncCommNode {
+    fn get_bloom_sync_state(&self) -> &Mutex<BloomSyncState>;
+
+    fn bloom_sync_tick(&self) {
+        let now = Instant::now();
+        let (full, window) = {
+            let mut state = self.get_bloom_sync_state().lock().unwrap();
+            if state
+                .last_round
+                .is_some_and(|at| now.duration_since(at) < BLOOM_SYNC_INTERVAL)
+            {
+                return;
+            }
+            state.last_round = Some(now);
+            let full = state.rounds.is_multiple_of(BLOOM_FULL_ROUND_EVERY);
+            state.rounds += 1;
+            let window = state.window();
+            state.recent.push_front(IntervalSet::new());
+            state.recent.truncate(BLOOM_WINDOW_ROUNDS);
+            (full, window)
+        };
+        let values = match full {
+            true => self.get_messages().lock().unwrap().to_owned(),
+            false => window,
+        };
+        let mut filter = BloomFilter::with_capacity(
+            values.len(),
+            BLOOM_FALSE_POSITIVE_RATE,
+            rng::random_u64() as u32,
+        );
+        for value in values.iter() {
+            filter.insert(&value);
+        }
+        let digest = MlstBodyReqBloomDigest {
+            msg_type: Self::get_route_bloom_digest(),
+            filter,
+            full,
+        };
+        let neighbor_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
+        for neighbor_id in neighbor_ids {
+            self.send(neighbor_id, &digest);
+        }
+    }
+
+    // Push back whatever the peer's filter does not cover, of all values after a full
+    // digest and of the recent ones otherwise.
+    fn process_bloom_digest(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqBloomDigest = serde_json::from_value(body_req).unwrap();
+        let values = match req_body.full {
+            true => self.get_messages().lock().unwrap().to_owned(),
+            false => self.get_bloom_sync_state().lock().unwrap().window(),
+        };
+        let missing: IntervalSet = values
+            .iter()
+            .filter(|message| !req_body.filter.contains(message))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+        self.log(&format!("BLOOM {} values missing at {}", missing.len(), src));
+        let bloom_values = MlstBodyReqBloomValues {
+            msg_type: Self::get_route_bloom_values(),
+            values: missing,
+        };
+        self.send(src, bloom_values);
+    }
+
+    fn process_bloom_values(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqBloomValues = serde_json::from_value(body_req).unwrap();
+        self.store_messages(&req_body.values);
+    }
+
+    fn get_route_bloom_digest() -> MsgTypeType;
+
+    fn get_route_bloom_values() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::bloom::BloomFilter;
+    use crate::interval_set::IntervalSet;
+    use crate::node::MsgTypeType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqBloomDigest {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub filter: BloomFilter,
+        pub full: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqBloomValues {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub values: IntervalSet,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AntiEntropyMode, BroadcastMode, Config};
+    use crate::sim::{Cluster, SimNode};
+    use crate::node::MsgType;
+    use crate::MlstService;
+
+    fn bloom_cluster() -> Cluster<MlstService> {
+        let mut cluster = Cluster::new(&["n1", "n2"], || {
+            let config = Config {
+                anti_entropy: AntiEntropyMode::Bloom,
+                ..Config::with_broadcast(BroadcastMode::Flood)
+            };
+            MlstService::with_config(config)
+        });
+        cluster.set_topology(&[("n1", &["n2"]), ("n2", &["n1"])]);
+        cluster
+    }
+
+    // One bloom round on every live node, without waiting out the interval.
+    fn round(cluster: &mut Cluster<MlstService>) {
+        for node in cluster.nodes.values() {
+            node.bloom_sync.lock().unwrap().last_round = None;
+        }
+        cluster.step();
+        while cluster.flush() > 0 {}
+    }
+
+    #[test]
+    fn digests_shrink_to_the_values_stored_lately() {
+        let mut cluster = bloom_cluster();
+        for node in cluster.nodes.values() {
+            node.store_messages(&(0..5000).collect());
+        }
+        for _ in 0..=BLOOM_WINDOW_ROUNDS {
+            round(&mut cluster);
+        }
+        let value: MsgType = 7000;
+        let node = &cluster.nodes["n1"];
+        node.store_message(value);
+        node.bloom_sync.lock().unwrap().last_round = None;
+        node.bloom_sync_tick();
+        let outbox = node.take_outbox();
+        let sent: serde_json::Value = serde_json::from_str(&outbox[0]).unwrap();
+        let digest: MlstBodyReqBloomDigest =
+            serde_json::from_value(sent["body"].to_owned()).unwrap();
+        assert!(!digest.full);
+        assert_eq!(digest.filter.bits.len(), 1);
+        assert!(digest.filter.contains(&value));
+
+        // the peer has nothing recent to offer, but gets the new value from n1's side
+        round(&mut cluster);
+        assert!(cluster.nodes["n2"].check_message(&value));
+    }
+
+    #[test]
+    fn full_rounds_close_gaps_older_than_the_window() {
+        let mut cluster = bloom_cluster();
+        cluster.down.insert("n2".to_string());
+        cluster.nodes["n1"].store_messages(&(0..100).collect());
+        for _ in 0..=BLOOM_WINDOW_ROUNDS {
+            round(&mut cluster);
+        }
+        // as if n2 had gone on with its rounds, its first full one included
+        let rounds = cluster.nodes["n1"].bloom_sync.lock().unwrap().rounds;
+        cluster.nodes["n2"].bloom_sync.lock().unwrap().rounds = rounds;
+        cluster.down.remove("n2");
+        round(&mut cluster);
+        assert!(cluster.nodes["n2"].messages.lock().unwrap().is_empty());
+        let n1_rounds = |cluster: &Cluster<MlstService>| {
+            cluster.nodes["n1"].bloom_sync.lock().unwrap().rounds
+        };
+        while !n1_rounds(&cluster).is_multiple_of(BLOOM_FULL_ROUND_EVERY) {
+            round(&mut cluster);
+        }
+        round(&mut cluster);
+        assert_eq!(cluster.nodes["n2"].messages.lock().unwrap().len(), 100);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::async_comm_node::{AsyncCommNode, MsgCachedKey};
+use crate::bracha::{Bracha, BrachaAction, BrachaId};
+use crate::config::ByzantineMode;
+use crate::node::proto::MlstAckBodyReq;
+use crate::node::{CommId, MsgType, MsgTypeType, NodeId};
+use proto::{MlstBodyReqBracha, MlstBodyRespBracha};
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct BrachaState {
+    // created on first use, once `init` told us how many nodes there are
+    pub protocol: Option<Bracha>,
+    pub next_seq: u64,
+}
+
+pub trait MlstBracha: AsyncCommNode {
+    fn get_bracha_state(&self) -> &Mutex<BrachaState>;
+
+    // Lets a node deliberately misbehave, to see agreement hold with up to f such nodes.
+    fn byzantine_mode(&self) -> ByzantineMode {
+        ByzantineMode::Honest
+    }
+
+    fn bracha_broadcast(&self, message: MsgType) {
+        let node_id = self.get_node_id().lock().unwrap().to_owned().unwrap();
+        let seq = {
+            let mut state = self.get_bracha_state().lock().unwrap();
+            state.next_seq += 1;
+            state.next_seq
+        };
+        let id = (node_id, seq);
+        self.bracha_multicast(Self::get_route_bracha_send(), &id, message);
+    }
+
+    // Sends to every other node and feeds our own copy straight into the state machine.
+    fn bracha_multicast(&self, msg_type: MsgTypeType, id: &BrachaId, value: MsgType) {
+        let node_id = self.get_node_id().lock().unwrap().to_owned().unwrap();
+        let node_ids = self.get_node_ids().lock().unwrap().to_owned();
+        let byzantine_mode = self.byzantine_mode();
+        if byzantine_mode == ByzantineMode::Silent && id.0 != node_id {
+            return;
+        }
+        for (index, peer) in node_ids.iter().enumerate() {
+            if peer == &node_id {
+                continue;
+            }
+            let value = match byzantine_mode {
+                ByzantineMode::Equivocate if index % 2 == 1 => !value,
+                _ => value,
+            };
+            let msg_id = self.next_msg_id();
+            let bracha = MlstBodyReqBracha {
+                msg_id,
+                msg_type: msg_type.to_owned(),
+                origin: id.0.to_owned(),
+                seq: id.1,
+                value,
+            };
+            self.await_communicate(msg_id, peer.to_owned(), bracha);
+        }
+        self.bracha_handle(&msg_type, id, &node_id, value);
+    }
+
+    fn bracha_handle(&self, msg_type: &MsgTypeType, id: &BrachaId, from: &NodeId, value: MsgType) {
+        let actions = {
+            let mut state = self.get_bracha_state().lock().unwrap();
+            let n = self.get_node_ids().lock().unwrap().len();
+            let protocol = state.protocol.get_or_insert_with(|| Bracha::new(n));
+            if msg_type == &Self::get_route_bracha_send() {
+                protocol.on_send(id, from, value)
+            } else if msg_type == &Self::get_route_bracha_echo() {
+                protocol.on_echo(id, from, value)
+            } else {
+                protocol.on_ready(id, from, value)
+            }
+        };
+        for action in actions {
+            match action {
+                BrachaAction::Echo(value) => {
+                    self.bracha_multicast(Self::get_route_bracha_echo(), id, value)
+                }
+                BrachaAction::Ready(value) => {
+                    self.bracha_multicast(Self::get_route_bracha_ready(), id, value)
+                }
+                BrachaAction::Deliver(value) => {
+                    self.log(&format!("BRACHA delivered {} from {}/{}", value, id.0, id.1));
+                    self.store_message(value);
+                }
+            }
+        }
+    }
+
+    // SEND, ECHO and READY share one body; the sender is taken from the envelope, never the body.
+    fn process_bracha(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqBracha = serde_json::from_value(body_req).unwrap();
+        let resp_body = MlstBodyRespBracha {
+            msg_type: Self::get_route_bracha_ok(),
+        };
+        self.reply(req_body.msg_id, src.to_owned(), resp_body);
+        let id = (req_body.origin, req_body.seq);
+        self.bracha_handle(&req_body.msg_type, &id, &src, req_body.value);
+    }
+
+    fn process_bracha_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstAckBodyReq = serde_json::from_value(body_req).unwrap();
+        let key = MsgCachedKey {
+            msg_id: req_body.in_reply_to.to_owned(),
+            dest: src,
+        };
+        self.ack_delivered(&key);
+    }
+
+    fn get_route_bracha_send() -> MsgTypeType;
+
+    fn get_route_bracha_echo() -> MsgTypeType;
+
+    fn get_route_bracha_ready() -> MsgTypeType;
+
+    fn get_route_bracha_ok() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::node::{MsgId, MsgType, MsgTypeType, NodeId};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqBracha {
+        pub msg_id: MsgId,
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub origin: NodeId,
+        pub seq: u64,
+        pub value: MsgType,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct MlstBodyRespBracha {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+}
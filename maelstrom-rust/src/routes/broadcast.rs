@@ -15,17 +15,10 @@ pub trait MlstBroadcast: AsyncCommNode {
         let req_body: MlstBodyReqBroadcast = serde_json::from_value(body_req).unwrap();
         let msg_id = &req_body.msg_id;
         let msg = req_body.message.to_owned();
-        if self.check_message(&msg) {
-            return;
-        }
-        self.store_message(msg);
-        // to_owned() her is because of interprocedural conflict. can be refactored to avoid copying
-        let neighbor_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
-        for neighbor_id in neighbor_ids.iter() {
-            if neighbor_id == &src {
-                continue;
-            };
-            self.await_communicate(req_body.msg_id, neighbor_id.to_owned(), &req_body);
+        // duplicates are acked as well, or their sender would retry them for good
+        if !self.check_message(&msg) {
+            self.store_message(msg);
+            self.spread_broadcast(&src, &req_body);
         }
         if comm_id.is_none() {
             self.log("do not reply");
@@ -40,6 +33,38 @@ pub trait MlstBroadcast: AsyncCommNode {
 
     fn get_route_broadcast() -> MsgTypeType;
 
+    // Override to pick another dissemination strategy for freshly stored messages.
+    fn spread_broadcast(&self, src: &NodeId, req_body: &MlstBodyReqBroadcast) {
+        self.flood_broadcast(src, req_body);
+    }
+
+    fn flood_broadcast(&self, src: &NodeId, req_body: &MlstBodyReqBroadcast) {
+        // to_owned() her is because of interprocedural conflict. can be refactored to avoid copying
+        let neighbor_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
+        for neighbor_id in neighbor_ids.iter() {
+            if neighbor_id == src {
+                continue;
+            };
+            // client msg_ids collide across clients, so every hop gets an id of our own
+            let msg_id = self.next_msg_id();
+            let forward = MlstBodyReqBroadcast {
+                msg_id,
+                msg_type: req_body.msg_type.to_owned(),
+                message: req_body.message,
+            };
+            if self.flood_awaits_ack() {
+                self.await_communicate(msg_id, neighbor_id.to_owned(), forward);
+            } else {
+                self.send(neighbor_id.to_owned(), forward);
+            }
+        }
+    }
+
+    // Without per-message acks the flood is fire-and-forget and some other sync fills the gaps.
+    fn flood_awaits_ack(&self) -> bool {
+        true
+    }
+
     fn process_broadcast_ok(
         &self,
         _msg_id: Option<MsgId>,
@@ -77,3 +102,43 @@ pub mod proto {
         pub msg_type: MsgTypeType,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{BroadcastMode, Config};
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+
+    const RING: [(&str, &[&str]); 5] = [
+        ("n1", &["n5", "n2"]),
+        ("n2", &["n1", "n3"]),
+        ("n3", &["n2", "n4"]),
+        ("n4", &["n3", "n5"]),
+        ("n5", &["n4", "n1"]),
+    ];
+
+    // On a cycle every value reaches most nodes twice; the second copy must be acked too,
+    // or it holds a window slot for good and starves what is queued behind it.
+    #[test]
+    fn flood_over_a_ring_acks_duplicate_hops() {
+        let ids: Vec<&str> = RING.iter().map(|(id, _)| *id).collect();
+        let mut cluster = Cluster::new(&ids, || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Flood))
+        });
+        cluster.set_topology(&RING);
+        for value in 0..60 {
+            let broadcast = json!({"type": "broadcast", "msg_id": value, "message": value});
+            cluster.request(ids[value as usize % ids.len()], broadcast);
+        }
+        cluster.settle();
+        for node in cluster.nodes.values() {
+            assert_eq!(node.messages.lock().unwrap().len(), 60);
+            assert!(node.pending_ack_ids.lock().unwrap().is_empty());
+            let windows = node.peer_windows.lock().unwrap();
+            assert!(windows.values().all(|window| window.queue.is_empty()));
+        }
+        let acked = cluster.replies.iter().filter(|(_, body)| body["type"] == "broadcast_ok");
+        assert_eq!(acked.count(), 60);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::async_comm_node::{AsyncCommNode, MsgCachedKey};
+use crate::node::proto::MlstAckBodyReq;
+use crate::node::{CommId, MsgType, MsgTypeType, NodeId};
+use crate::vclock::VectorClock;
+use proto::{MlstBodyReqCausalBroadcast, MlstBodyRespCausalBroadcast};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct CausalState {
+    // per origin, how many of its messages were delivered to the store
+    pub delivered: VectorClock,
+    pub pending: Vec<MlstBodyReqCausalBroadcast>,
+    // (origin, sequence number) of every message received, delivered or not
+    pub seen: HashSet<(NodeId, u64)>,
+}
+
+impl CausalState {
+    fn deliverable(&self, causal: &MlstBodyReqCausalBroadcast) -> bool {
+        causal.clock.0.iter().all(|(node_id, value)| {
+            if node_id == &causal.origin {
+                *value == self.delivered.get(node_id) + 1
+            } else {
+                *value <= self.delivered.get(node_id)
+            }
+        })
+    }
+}
+
+// Every message carries the vector clock of what its origin had delivered when sending it,
+// and is only stored once all of those predecessors are stored here too. The store therefore
+// always holds a causally complete set, which is what `read` returns.
+pub trait MlstCausalBroadcast: AsyncCommNode {
+    fn get_causal_state(&self) -> &Mutex<CausalState>;
+
+    fn causal_broadcast(&self, message: MsgType) {
+        let node_id = self.get_node_id().lock().unwrap().to_owned().unwrap();
+        let causal = {
+            let mut state = self.get_causal_state().lock().unwrap();
+            let seq = state.delivered.increment(&node_id);
+            state.seen.insert((node_id.to_owned(), seq));
+            MlstBodyReqCausalBroadcast {
+                msg_id: 0,
+                msg_type: Self::get_route_causal_broadcast(),
+                origin: node_id.to_owned(),
+                message,
+                clock: state.delivered.to_owned(),
+            }
+        };
+        self.causal_forward(&causal, &node_id);
+    }
+
+    fn causal_forward(&self, causal: &MlstBodyReqCausalBroadcast, src: &NodeId) {
+        let neighbor_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
+        for neighbor_id in neighbor_ids.iter().filter(|p| *p != src && *p != &causal.origin) {
+            let msg_id = self.next_msg_id();
+            let forward = MlstBodyReqCausalBroadcast {
+                msg_id,
+                msg_type: causal.msg_type.to_owned(),
+                origin: causal.origin.to_owned(),
+                message: causal.message,
+                clock: causal.clock.to_owned(),
+            };
+            self.await_communicate(msg_id, neighbor_id.to_owned(), forward);
+        }
+    }
+
+    fn process_causal_broadcast(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqCausalBroadcast = serde_json::from_value(body_req).unwrap();
+        let resp_body = MlstBodyRespCausalBroadcast {
+            msg_type: Self::get_route_causal_broadcast_ok(),
+        };
+        self.reply(req_body.msg_id, src.to_owned(), resp_body);
+        let id = (req_body.origin.to_owned(), req_body.clock.get(&req_body.origin));
+        if !self.get_causal_state().lock().unwrap().seen.insert(id) {
+            return;
+        }
+        self.causal_forward(&req_body, &src);
+        self.get_causal_state().lock().unwrap().pending.push(req_body);
+        self.causal_deliver();
+    }
+
+    // Deliver buffered messages until none of the rest has all its predecessors.
+    fn causal_deliver(&self) {
+        let mut state = self.get_causal_state().lock().unwrap();
+        while let Some(position) = state.pending.iter().position(|c| state.deliverable(c)) {
+            let causal = state.pending.swap_remove(position);
+            state.delivered.increment(&causal.origin);
+            self.store_message(causal.message);
+        }
+        if !state.pending.is_empty() {
+            self.log(&format!("CAUSAL {} messages buffered", state.pending.len()));
+        }
+    }
+
+    fn process_causal_broadcast_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstAckBodyReq = serde_json::from_value(body_req).unwrap();
+        let key = MsgCachedKey {
+            msg_id: req_body.in_reply_to.to_owned(),
+            dest: src,
+        };
+        self.ack_delivered(&key);
+    }
+
+    fn get_route_causal_broadcast() -> MsgTypeType;
+
+    fn get_route_causal_broadcast_ok() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::node::{MsgId, MsgType, MsgTypeType, NodeId};
+    use crate::vclock::VectorClock;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqCausalBroadcast {
+        pub msg_id: MsgId,
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub origin: NodeId,
+        pub message: MsgType,
+        pub clock: VectorClock,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct MlstBodyRespCausalBroadcast {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BroadcastMode, Config};
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+
+    fn causal(origin: &str, message: MsgType, clock: serde_json::Value) -> serde_json::Value {
+        json!({
+            "type": "causal_broadcast",
+            "msg_id": message,
+            "origin": origin,
+            "message": message,
+            "clock": clock,
+        })
+    }
+
+    // n2 sent 20 after delivering 10 from n1; n3 gets 20 first and must hold it back.
+    #[test]
+    fn message_waits_for_the_predecessor_it_saw() {
+        let mut cluster = Cluster::new(&["n1", "n2", "n3"], || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Causal))
+        });
+        let n3 = &cluster.nodes["n3"];
+        let later = causal("n2", 20, json!({"n1": 1, "n2": 1}));
+        n3.process_causal_broadcast(None, "n2".to_string(), "n3".to_string(), later);
+        assert!(n3.messages.lock().unwrap().is_empty());
+        assert_eq!(n3.causal.lock().unwrap().pending.len(), 1);
+        cluster.request("n3", json!({"type": "read", "msg_id": 1}));
+        cluster.flush();
+        assert_eq!(cluster.replies.last().unwrap().1["messages"], json!([]));
+
+        let n3 = &cluster.nodes["n3"];
+        let earlier = causal("n1", 10, json!({"n1": 1}));
+        n3.process_causal_broadcast(None, "n1".to_string(), "n3".to_string(), earlier);
+        assert!(n3.causal.lock().unwrap().pending.is_empty());
+        cluster.request("n3", json!({"type": "read", "msg_id": 2}));
+        cluster.flush();
+        assert_eq!(cluster.replies.last().unwrap().1["messages"], json!([10, 20]));
+    }
+}
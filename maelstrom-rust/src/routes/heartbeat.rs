@@ -0,0 +1,94 @@
+use crate::failure_detector::{PeerEvent, PhiAccrualDetector};
+use crate::node::{CommId, MsgTypeType, Node, NodeId};
+use proto::MlstBodyReqHeartbeat;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Only peers we have not sent anything to for this long get an explicit heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(200);
+
+pub trait MlstFailureDetector: Node {
+    fn get_failure_detector(&self) -> &Mutex<PhiAccrualDetector>;
+
+    fn record_arrival(&self, src: &NodeId) {
+        // clients are not monitored
+        if !self.get_node_ids().lock().unwrap().contains(src) {
+            return;
+        }
+        self.get_failure_detector()
+            .lock()
+            .unwrap()
+            .heartbeat(src, Instant::now());
+    }
+
+    fn record_departure(&self, dest: &NodeId) {
+        self.get_failure_detector()
+            .lock()
+            .unwrap()
+            .record_sent(dest, Instant::now());
+    }
+
+    fn peer_suspected(&self, peer: &NodeId) -> bool {
+        self.get_failure_detector().lock().unwrap().is_suspected(peer)
+    }
+
+    // The peers watched, and heartbeated while idle: the neighbors, unless a protocol talks
+    // to other nodes directly.
+    fn monitored_peers(&self) -> Vec<NodeId> {
+        self.get_neighbor_ids().lock().unwrap().to_owned()
+    }
+
+    fn failure_detector_tick(&self) {
+        let now = Instant::now();
+        let peers = self.monitored_peers();
+        let idle: Vec<NodeId> = {
+            let mut detector = self.get_failure_detector().lock().unwrap();
+            detector.check(&peers, now);
+            peers
+                .into_iter()
+                .filter(|peer| {
+                    detector
+                        .last_sent(peer)
+                        .is_none_or(|at| now.duration_since(at) >= HEARTBEAT_INTERVAL)
+                })
+                .collect()
+        };
+        // the detector lock is released first: sending records the departure
+        for peer in idle {
+            let heartbeat = MlstBodyReqHeartbeat {
+                msg_type: Self::get_route_heartbeat(),
+            };
+            self.send(peer, heartbeat);
+        }
+    }
+
+    fn on_peer_event(&self, event: &PeerEvent) {
+        match event {
+            PeerEvent::Suspect(peer) => self.log(&format!("SUSPECT {}", peer)),
+            PeerEvent::Recover(peer) => self.log(&format!("RECOVER {}", peer)),
+        }
+    }
+
+    // The arrival itself was already recorded when the message was read.
+    fn process_heartbeat(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        _body_req: serde_json::Value,
+    ) {
+    }
+
+    fn get_route_heartbeat() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::node::MsgTypeType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHeartbeat {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+}
@@ -0,0 +1,598 @@
+use crate::node::{CommId, MsgTypeType, Node, NodeId};
+use crate::rng;
+use proto::{
+    MlstBodyReqHpvConnect, MlstBodyReqHpvDisconnect, MlstBodyReqHpvForwardJoin,
+    MlstBodyReqHpvHeartbeat, MlstBodyReqHpvJoin, MlstBodyReqHpvNeighbor,
+    MlstBodyReqHpvNeighborReply, MlstBodyReqHpvShuffle, MlstBodyReqHpvShuffleReply,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const ACTIVE_VIEW_SIZE: usize = 4;
+const PASSIVE_VIEW_SIZE: usize = 16;
+// active and passive random walk lengths of FORWARDJOIN
+const ACTIVE_RANDOM_WALK_LENGTH: u32 = 4;
+const PASSIVE_RANDOM_WALK_LENGTH: u32 = 2;
+const SHUFFLE_ACTIVE_COUNT: usize = 2;
+const SHUFFLE_PASSIVE_COUNT: usize = 3;
+const SHUFFLE_INTERVAL: Duration = Duration::from_millis(1000);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(300);
+// an active peer silent for this long is considered failed
+const PEER_TIMEOUT: Duration = Duration::from_millis(2000);
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(1000);
+
+#[derive(Default)]
+pub struct HyParViewState {
+    pub active: Vec<NodeId>,
+    pub passive: Vec<NodeId>,
+    pub last_seen: HashMap<NodeId, Instant>,
+    // passive peer asked to become an active one, and when
+    pub pending_neighbor: Option<(NodeId, Instant)>,
+    pub last_join: Option<Instant>,
+    pub last_shuffle: Option<Instant>,
+    pub last_heartbeat: Option<Instant>,
+}
+
+impl HyParViewState {
+    fn add_passive(&mut self, peer: &NodeId, node_id: &NodeId) {
+        if peer == node_id || self.active.contains(peer) || self.passive.contains(peer) {
+            return;
+        }
+        if self.passive.len() >= PASSIVE_VIEW_SIZE {
+            self.passive.remove(rng::random_index(self.passive.len()));
+        }
+        self.passive.push(peer.to_owned());
+    }
+
+    // Returns the peer evicted to make room, if any.
+    fn add_active(&mut self, peer: &NodeId) -> Option<NodeId> {
+        if self.active.contains(peer) {
+            return None;
+        }
+        let dropped = if self.active.len() >= ACTIVE_VIEW_SIZE {
+            Some(self.active.remove(rng::random_index(self.active.len())))
+        } else {
+            None
+        };
+        self.passive.retain(|p| p != peer);
+        self.active.push(peer.to_owned());
+        self.last_seen.insert(peer.to_owned(), Instant::now());
+        dropped
+    }
+
+    fn remove_active(&mut self, peer: &NodeId) -> bool {
+        let len = self.active.len();
+        self.active.retain(|p| p != peer);
+        len != self.active.len()
+    }
+
+    fn random_active_except(&self, excluded: &[&NodeId]) -> Option<NodeId> {
+        let candidates: Vec<NodeId> = self
+            .active
+            .iter()
+            .filter(|p| !excluded.contains(p))
+            .cloned()
+            .collect();
+        rng::choose(&candidates)
+    }
+}
+
+pub trait MlstHyParView: Node {
+    fn get_hyparview_state(&self) -> &Mutex<HyParViewState>;
+
+    fn hpv_node_id(&self) -> NodeId {
+        self.get_node_id().lock().unwrap().to_owned().unwrap()
+    }
+
+    // The active view is what the rest of the node sees as its neighbors. Overridden where
+    // a change of neighbors needs more than the new list.
+    fn hpv_publish(&self, state: &HyParViewState) {
+        self.set_neighbor_ids(state.active.to_owned());
+    }
+
+    fn hpv_add_active(&self, state: &mut HyParViewState, peer: &NodeId, notify: bool) {
+        let node_id = self.hpv_node_id();
+        if peer == &node_id || state.active.contains(peer) {
+            return;
+        }
+        if let Some(dropped) = state.add_active(peer) {
+            let disconnect = MlstBodyReqHpvDisconnect {
+                msg_type: Self::get_route_hpv_disconnect(),
+            };
+            self.send(dropped.to_owned(), disconnect);
+            state.add_passive(&dropped, &node_id);
+        }
+        if notify {
+            let connect = MlstBodyReqHpvConnect {
+                msg_type: Self::get_route_hpv_connect(),
+            };
+            self.send(peer.to_owned(), connect);
+        }
+        self.log(&format!("HYPARVIEW active view: {:?}", state.active));
+        self.hpv_publish(state);
+    }
+
+    fn hpv_remove_failed(&self, state: &mut HyParViewState, peer: &NodeId) {
+        if state.remove_active(peer) {
+            self.log(&format!("HYPARVIEW peer failed: {}", peer));
+            self.hpv_publish(state);
+        }
+    }
+
+    // Lets an external failure detector evict a peer before the view's own timeout does.
+    fn hpv_peer_failed(&self, peer: &NodeId) {
+        let mut state = self.get_hyparview_state().lock().unwrap();
+        self.hpv_remove_failed(&mut state, peer);
+    }
+
+    fn hpv_integrate(&self, state: &mut HyParViewState, nodes: &[NodeId]) {
+        let node_id = self.hpv_node_id();
+        for node in nodes {
+            state.add_passive(node, &node_id);
+        }
+    }
+
+    fn hpv_touch(&self, src: &NodeId) -> std::sync::MutexGuard<'_, HyParViewState> {
+        let mut state = self.get_hyparview_state().lock().unwrap();
+        state.last_seen.insert(src.to_owned(), Instant::now());
+        state
+    }
+
+    fn process_hpv_join(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        _body_req: serde_json::Value,
+    ) {
+        self.log("HYPARVIEW JOIN");
+        let mut state = self.hpv_touch(&src);
+        self.hpv_add_active(&mut state, &src, true);
+        for peer in state.active.iter().filter(|p| *p != &src) {
+            let forward_join = MlstBodyReqHpvForwardJoin {
+                msg_type: Self::get_route_hpv_forward_join(),
+                new_node: src.to_owned(),
+                ttl: ACTIVE_RANDOM_WALK_LENGTH,
+            };
+            self.send(peer.to_owned(), forward_join);
+        }
+    }
+
+    fn process_hpv_forward_join(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqHpvForwardJoin = serde_json::from_value(body_req).unwrap();
+        let node_id = self.hpv_node_id();
+        let new_node = req_body.new_node;
+        let mut state = self.hpv_touch(&src);
+        if new_node == node_id {
+            return;
+        }
+        if req_body.ttl == 0 || state.active.len() <= 1 {
+            self.hpv_add_active(&mut state, &new_node, true);
+            return;
+        }
+        if req_body.ttl == PASSIVE_RANDOM_WALK_LENGTH {
+            state.add_passive(&new_node, &node_id);
+        }
+        match state.random_active_except(&[&src, &new_node]) {
+            Some(next) => {
+                let forward_join = MlstBodyReqHpvForwardJoin {
+                    msg_type: Self::get_route_hpv_forward_join(),
+                    new_node,
+                    ttl: req_body.ttl - 1,
+                };
+                self.send(next, forward_join);
+            }
+            None => self.hpv_add_active(&mut state, &new_node, true),
+        }
+    }
+
+    fn process_hpv_connect(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        _body_req: serde_json::Value,
+    ) {
+        let mut state = self.hpv_touch(&src);
+        self.hpv_add_active(&mut state, &src, false);
+    }
+
+    fn process_hpv_disconnect(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        _body_req: serde_json::Value,
+    ) {
+        let node_id = self.hpv_node_id();
+        let mut state = self.hpv_touch(&src);
+        if state.remove_active(&src) {
+            state.add_passive(&src, &node_id);
+            self.hpv_publish(&state);
+        }
+    }
+
+    fn process_hpv_neighbor(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqHpvNeighbor = serde_json::from_value(body_req).unwrap();
+        let mut state = self.hpv_touch(&src);
+        let accepted = req_body.high_priority || state.active.len() < ACTIVE_VIEW_SIZE;
+        if accepted {
+            self.hpv_add_active(&mut state, &src, false);
+        }
+        let neighbor_reply = MlstBodyReqHpvNeighborReply {
+            msg_type: Self::get_route_hpv_neighbor_reply(),
+            accepted,
+        };
+        self.send(src, neighbor_reply);
+    }
+
+    fn process_hpv_neighbor_reply(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqHpvNeighborReply = serde_json::from_value(body_req).unwrap();
+        let mut state = self.hpv_touch(&src);
+        if matches!(&state.pending_neighbor, Some((peer, _)) if peer == &src) {
+            state.pending_neighbor = None;
+        }
+        if req_body.accepted {
+            self.hpv_add_active(&mut state, &src, false);
+        }
+    }
+
+    fn process_hpv_shuffle(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqHpvShuffle = serde_json::from_value(body_req).unwrap();
+        let mut state = self.hpv_touch(&src);
+        if req_body.ttl > 0 && state.active.len() > 1 {
+            if let Some(next) = state.random_active_except(&[&src, &req_body.origin]) {
+                let shuffle = MlstBodyReqHpvShuffle {
+                    ttl: req_body.ttl - 1,
+                    ..req_body
+                };
+                self.send(next, shuffle);
+                return;
+            }
+        }
+        let shuffle_reply = MlstBodyReqHpvShuffleReply {
+            msg_type: Self::get_route_hpv_shuffle_reply(),
+            nodes: rng::sample(&state.passive, req_body.nodes.len()),
+        };
+        self.send(req_body.origin.to_owned(), shuffle_reply);
+        self.hpv_integrate(&mut state, &req_body.nodes);
+    }
+
+    fn process_hpv_shuffle_reply(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqHpvShuffleReply = serde_json::from_value(body_req).unwrap();
+        let mut state = self.hpv_touch(&src);
+        self.hpv_integrate(&mut state, &req_body.nodes);
+    }
+
+    fn process_hpv_heartbeat(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        _body_req: serde_json::Value,
+    ) {
+        drop(self.hpv_touch(&src));
+    }
+
+    fn hyparview_tick(&self) {
+        let node_id = match self.get_node_id().lock().unwrap().to_owned() {
+            Some(node_id) => node_id,
+            None => return,
+        };
+        let now = Instant::now();
+        let mut state = self.get_hyparview_state().lock().unwrap();
+
+        // replace active peers that went silent
+        let failed: Vec<NodeId> = state
+            .active
+            .iter()
+            .filter(|p| {
+                state
+                    .last_seen
+                    .get(*p)
+                    .is_none_or(|seen| now.duration_since(*seen) > PEER_TIMEOUT)
+            })
+            .cloned()
+            .collect();
+        for peer in failed.iter() {
+            self.hpv_remove_failed(&mut state, peer);
+        }
+
+        if state.active.is_empty() && state.passive.is_empty() {
+            if state
+                .last_join
+                .is_some_and(|at| now.duration_since(at) < REQUEST_TIMEOUT)
+            {
+                return;
+            }
+            let others: Vec<NodeId> = self
+                .get_node_ids()
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|p| *p != &node_id)
+                .cloned()
+                .collect();
+            if let Some(contact) = rng::choose(&others) {
+                let join = MlstBodyReqHpvJoin {
+                    msg_type: Self::get_route_hpv_join(),
+                };
+                self.send(contact, join);
+                state.last_join = Some(now);
+            }
+            return;
+        }
+
+        if let Some((peer, asked_at)) = state.pending_neighbor.to_owned() {
+            if now.duration_since(asked_at) > REQUEST_TIMEOUT {
+                state.passive.retain(|p| p != &peer);
+                state.pending_neighbor = None;
+            }
+        }
+        if state.active.len() < ACTIVE_VIEW_SIZE && state.pending_neighbor.is_none() {
+            if let Some(peer) = rng::choose(&state.passive) {
+                let neighbor = MlstBodyReqHpvNeighbor {
+                    msg_type: Self::get_route_hpv_neighbor(),
+                    high_priority: state.active.is_empty(),
+                };
+                self.send(peer.to_owned(), neighbor);
+                state.pending_neighbor = Some((peer, now));
+            }
+        }
+
+        if state
+            .last_heartbeat
+            .is_none_or(|at| now.duration_since(at) >= HEARTBEAT_INTERVAL)
+        {
+            for peer in state.active.iter() {
+                let heartbeat = MlstBodyReqHpvHeartbeat {
+                    msg_type: Self::get_route_hpv_heartbeat(),
+                };
+                self.send(peer.to_owned(), heartbeat);
+            }
+            state.last_heartbeat = Some(now);
+        }
+
+        if state
+            .last_shuffle
+            .is_none_or(|at| now.duration_since(at) >= SHUFFLE_INTERVAL)
+        {
+            if let Some(peer) = rng::choose(&state.active) {
+                let mut nodes = vec![node_id.to_owned()];
+                nodes.extend(rng::sample(&state.active, SHUFFLE_ACTIVE_COUNT));
+                nodes.extend(rng::sample(&state.passive, SHUFFLE_PASSIVE_COUNT));
+                let shuffle = MlstBodyReqHpvShuffle {
+                    msg_type: Self::get_route_hpv_shuffle(),
+                    origin: node_id,
+                    nodes,
+                    ttl: ACTIVE_RANDOM_WALK_LENGTH,
+                };
+                self.send(peer, shuffle);
+            }
+            state.last_shuffle = Some(now);
+        }
+    }
+
+    fn get_route_hpv_join() -> MsgTypeType;
+
+    fn get_route_hpv_forward_join() -> MsgTypeType;
+
+    fn get_route_hpv_connect() -> MsgTypeType;
+
+    fn get_route_hpv_disconnect() -> MsgTypeType;
+
+    fn get_route_hpv_neighbor() -> MsgTypeType;
+
+    fn get_route_hpv_neighbor_reply() -> MsgTypeType;
+
+    fn get_route_hpv_shuffle() -> MsgTypeType;
+
+    fn get_route_hpv_shuffle_reply() -> MsgTypeType;
+
+    fn get_route_hpv_heartbeat() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::node::{MsgTypeType, NodeId};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvJoin {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvForwardJoin {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub new_node: NodeId,
+        pub ttl: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvConnect {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvDisconnect {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvNeighbor {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub high_priority: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvNeighborReply {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub accepted: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvShuffle {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub origin: NodeId,
+        pub nodes: Vec<NodeId>,
+        pub ttl: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvShuffleReply {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub nodes: Vec<NodeId>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqHpvHeartbeat {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_comm_node::AsyncCommNode;
+    use crate::config::{BroadcastMode, Config, MembershipMode};
+    use crate::sim::{Cluster, SimNode};
+    use crate::MlstService;
+    use serde_json::json;
+
+    fn peers(ids: &[&str]) -> Vec<NodeId> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn hyparview_cluster(ids: &[&str]) -> Cluster<MlstService> {
+        Cluster::new(ids, || {
+            let config = Config {
+                membership: MembershipMode::HyParView,
+                ..Config::with_broadcast(BroadcastMode::Flood)
+            };
+            MlstService::with_config(config)
+        })
+    }
+
+    #[test]
+    fn full_active_view_evicts_a_peer() {
+        let mut state = HyParViewState::default();
+        for peer in peers(&["n1", "n2", "n3", "n4"]) {
+            assert_eq!(state.add_active(&peer), None);
+        }
+        let newcomer = "n5".to_string();
+        state.add_passive(&newcomer, &"n0".to_string());
+        let dropped = state.add_active(&newcomer).unwrap();
+        assert_eq!(state.active.len(), ACTIVE_VIEW_SIZE);
+        assert!(state.active.contains(&newcomer));
+        assert!(!state.active.contains(&dropped));
+        assert!(state.passive.is_empty());
+    }
+
+    #[test]
+    fn passive_view_skips_known_peers_and_stays_bounded() {
+        let node_id = "n0".to_string();
+        let mut state = HyParViewState::default();
+        state.add_active(&"n1".to_string());
+        state.add_passive(&node_id, &node_id);
+        state.add_passive(&"n1".to_string(), &node_id);
+        assert!(state.passive.is_empty());
+        for i in 2..40 {
+            state.add_passive(&format!("n{}", i), &node_id);
+            state.add_passive(&format!("n{}", i), &node_id);
+        }
+        assert_eq!(state.passive.len(), PASSIVE_VIEW_SIZE);
+        let unique: std::collections::HashSet<&NodeId> = state.passive.iter().collect();
+        assert_eq!(unique.len(), PASSIVE_VIEW_SIZE);
+    }
+
+    #[test]
+    fn shuffle_walks_on_then_swaps_passive_peers_with_the_origin() {
+        let cluster = hyparview_cluster(&["n1", "n2", "n3", "n4", "n5"]);
+        let node = &cluster.nodes["n1"];
+        node.hyparview.lock().unwrap().active = peers(&["n2", "n3"]);
+        let shuffle =
+            json!({"type": "hpv_shuffle", "origin": "n2", "nodes": ["n2", "n5"], "ttl": 2});
+        node.process_hpv_shuffle(None, "n2".to_string(), "n1".to_string(), shuffle);
+        let sent: serde_json::Value = serde_json::from_str(&node.take_outbox()[0]).unwrap();
+        assert_eq!(sent["dest"], "n3");
+        assert_eq!(sent["body"]["ttl"], 1);
+
+        node.hyparview.lock().unwrap().passive = peers(&["n4"]);
+        let shuffle =
+            json!({"type": "hpv_shuffle", "origin": "n2", "nodes": ["n2", "n5"], "ttl": 0});
+        node.process_hpv_shuffle(None, "n3".to_string(), "n1".to_string(), shuffle);
+        let sent: serde_json::Value = serde_json::from_str(&node.take_outbox()[0]).unwrap();
+        assert_eq!(sent["dest"], "n2");
+        assert_eq!(sent["body"], json!({"type": "hpv_shuffle_reply", "nodes": ["n4"]}));
+        // the origin is active already, only the peer it knew joins the passive view
+        let mut passive = node.hyparview.lock().unwrap().passive.to_owned();
+        passive.sort();
+        assert_eq!(passive, peers(&["n4", "n5"]));
+    }
+
+    #[test]
+    fn passive_peer_is_promoted_and_gets_the_backlog() {
+        let mut cluster = hyparview_cluster(&["n1", "n2"]);
+        cluster.request("n1", json!({"type": "broadcast", "msg_id": 1, "message": 7}));
+        cluster.nodes["n1"].hyparview.lock().unwrap().passive = peers(&["n2"]);
+        cluster.nodes["n1"].hyparview_tick();
+        while cluster.flush() > 0 {}
+        for (node_id, peer) in [("n1", "n2"), ("n2", "n1")] {
+            let node = &cluster.nodes[node_id];
+            assert_eq!(node.hyparview.lock().unwrap().active, peers(&[peer]));
+            assert_eq!(*node.neighbor_ids.lock().unwrap(), peers(&[peer]));
+        }
+        // the new neighbor got a sync of what n1 had, sent on the next tick
+        cluster.settle();
+        assert!(cluster.nodes["n2"].check_message(&7));
+
+        // a failed active peer stops being a neighbor and stops being retried
+        cluster.down.insert("n2".to_string());
+        cluster.request("n1", json!({"type": "broadcast", "msg_id": 2, "message": 8}));
+        let node = &cluster.nodes["n1"];
+        assert!(!node.pending_ack_ids.lock().unwrap().is_empty());
+        node.hpv_peer_failed(&"n2".to_string());
+        assert!(node.neighbor_ids.lock().unwrap().is_empty());
+        assert!(node.pending_ack_ids.lock().unwrap().is_empty());
+    }
+}
@@ -12,6 +12,7 @@ pub trait MlstInit: Node {
         self.log("INIT");
         let req_body: MlstBodyReqInit = serde_json::from_value(body_req).unwrap();
         self.set_node_id(req_body.node_id.to_owned());
+        self.set_node_ids(req_body.node_ids.to_owned());
         let resp_body = MlstBodyRespInit {
             msg_type: "init_ok".to_string(),
         };
@@ -29,7 +30,7 @@ pub mod proto {
     pub struct MlstBodyReqInit {
         pub msg_id: MsgId,
         pub node_id: NodeId,
-        pub node_ids: Vec<String>,
+        pub node_ids: Vec<NodeId>,
     }
 
     #[derive(Serialize, Deserialize, Clone)]
@@ -0,0 +1,228 @@
+use crate::interval_set::IntervalSet;
+use crate::merkle::{MerkleTree, MERKLE_DEPTH};
+use crate::async_comm_node::AsyncCommNode;
+use crate::node::{CommId, MsgTypeType, NodeId};
+use proto::{
+    MlstBodyReqMerkleDigest, MlstBodyReqMerkleLeaves, MlstBodyReqMerkleValues, MlstMerkleLeaf,
+    MlstMerkleNode,
+};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const ANTI_ENTROPY_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Default)]
+pub struct MerkleSyncState {
+    // over the stored messages, updated as they are stored
+    pub tree: MerkleTree,
+    pub last_round: Option<Instant>,
+    // neighbors are synced with one at a time, round robin
+    pub next_peer: usize,
+}
+
+pub trait MlstMerkleSync: AsyncCommNode {
+    fn get_merkle_sync_state(&self) -> &Mutex<MerkleSyncState>;
+
+    fn merkle_sync_tick(&self) {
+        let now = Instant::now();
+        let (peer, root) = {
+            let mut state = self.get_merkle_sync_state().lock().unwrap();
+            if state
+                .last_round
+                .is_some_and(|at| now.duration_since(at) < ANTI_ENTROPY_INTERVAL)
+            {
+                return;
+            }
+            state.last_round = Some(now);
+            let neighbor_ids = self.get_neighbor_ids().lock().unwrap();
+            if neighbor_ids.is_empty() {
+                return;
+            }
+            state.next_peer = (state.next_peer + 1) % neighbor_ids.len();
+            let root = MlstMerkleNode {
+                level: 0,
+                index: 0,
+                hash: state.tree.hash(0, 0),
+            };
+            (neighbor_ids[state.next_peer].to_owned(), root)
+        };
+        let digest = MlstBodyReqMerkleDigest {
+            msg_type: Self::get_route_merkle_digest(),
+            nodes: vec![root],
+        };
+        self.send(peer, digest);
+    }
+
+    // Compare the peer's hashes with ours and descend only into the ranges that differ.
+    // Both sides run this, so each exchange goes one level deeper until leaves are reached.
+    fn process_merkle_digest(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqMerkleDigest = serde_json::from_value(body_req).unwrap();
+        let state = self.get_merkle_sync_state().lock().unwrap();
+        let tree = &state.tree;
+        let mut children = Vec::new();
+        let mut leaves = Vec::new();
+        for node in req_body.nodes.iter() {
+            if tree.hash(node.level, node.index) == node.hash {
+                continue;
+            }
+            if node.level == MERKLE_DEPTH {
+                leaves.push(MlstMerkleLeaf {
+                    index: node.index,
+                    values: tree.leaf_values(node.index).to_owned(),
+                });
+                continue;
+            }
+            for index in [node.index * 2, node.index * 2 + 1] {
+                children.push(MlstMerkleNode {
+                    level: node.level + 1,
+                    index,
+                    hash: tree.hash(node.level + 1, index),
+                });
+            }
+        }
+        drop(state);
+        if !children.is_empty() {
+            let digest = MlstBodyReqMerkleDigest {
+                msg_type: Self::get_route_merkle_digest(),
+                nodes: children,
+            };
+            self.send(src.to_owned(), digest);
+        }
+        if !leaves.is_empty() {
+            self.log(&format!("MERKLE {} leaves differ with {}", leaves.len(), src));
+            let merkle_leaves = MlstBodyReqMerkleLeaves {
+                msg_type: Self::get_route_merkle_leaves(),
+                leaves,
+            };
+            self.send(src, merkle_leaves);
+        }
+    }
+
+    // Take what the peer has in the differing leaves and push back what it lacks.
+    fn process_merkle_leaves(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqMerkleLeaves = serde_json::from_value(body_req).unwrap();
+        let mut missing = IntervalSet::new();
+        {
+            let state = self.get_merkle_sync_state().lock().unwrap();
+            for leaf in req_body.leaves.iter() {
+                missing.extend(&state.tree.leaf_values(leaf.index).difference(&leaf.values));
+            }
+        }
+        for leaf in req_body.leaves.iter() {
+            self.store_messages(&leaf.values);
+        }
+        if missing.is_empty() {
+            return;
+        }
+        let merkle_values = MlstBodyReqMerkleValues {
+            msg_type: Self::get_route_merkle_values(),
+            values: missing,
+        };
+        self.send(src, merkle_values);
+    }
+
+    fn process_merkle_values(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqMerkleValues = serde_json::from_value(body_req).unwrap();
+        self.store_messages(&req_body.values);
+    }
+
+    fn get_route_merkle_digest() -> MsgTypeType;
+
+    fn get_route_merkle_leaves() -> MsgTypeType;
+
+    fn get_route_merkle_values() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::interval_set::IntervalSet;
+    use crate::merkle::MerkleHash;
+    use crate::node::MsgTypeType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstMerkleNode {
+        pub level: u32,
+        pub index: usize,
+        pub hash: MerkleHash,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstMerkleLeaf {
+        pub index: usize,
+        pub values: IntervalSet,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqMerkleDigest {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub nodes: Vec<MlstMerkleNode>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqMerkleLeaves {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub leaves: Vec<MlstMerkleLeaf>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqMerkleValues {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub values: IntervalSet,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_comm_node::AsyncCommNode;
+    use crate::config::{AntiEntropyMode, BroadcastMode, Config};
+    use crate::interval_set::IntervalSet;
+    use crate::sim::Cluster;
+    use crate::MlstService;
+
+    #[test]
+    fn one_round_repairs_both_sides() {
+        let mut cluster = Cluster::new(&["n1", "n2"], || {
+            let config = Config {
+                anti_entropy: AntiEntropyMode::Merkle,
+                ..Config::with_broadcast(BroadcastMode::Flood)
+            };
+            MlstService::with_config(config)
+        });
+        cluster.set_topology(&[("n1", &["n2"]), ("n2", &["n1"])]);
+        cluster.nodes["n1"].store_messages(&(0..500).collect());
+        cluster.nodes["n2"].store_messages(&(400..600).collect());
+        cluster.nodes["n2"].store_message(1000);
+        cluster.settle();
+        let expected: IntervalSet = (0..600).chain([1000]).collect();
+        let roots: Vec<_> = cluster
+            .nodes
+            .values()
+            .map(|node| {
+                assert_eq!(*node.messages.lock().unwrap(), expected);
+                node.merkle_sync.lock().unwrap().tree.hash(0, 0)
+            })
+            .collect();
+        assert_eq!(roots[0], roots[1]);
+    }
+}
@@ -0,0 +1,675 @@
+use crate::async_comm_node::AsyncCommNode;
+use crate::node::{CommId, MsgType, MsgTypeType, NodeId};
+use proto::{
+    MlstBodyReqOrdAccept, MlstBodyReqOrdCommit, MlstBodyReqOrdNack, MlstBodyReqOrdSubmit,
+    MlstBodyReqOrdSync, MlstBodyReqOrdTakeover, MlstBodyRespOrdAccept, MlstBodyRespOrdTakeover,
+    MlstOrdEntry,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const SUBMIT_TIMEOUT: Duration = Duration::from_millis(1000);
+const SYNC_INTERVAL: Duration = Duration::from_millis(500);
+const NACK_INTERVAL: Duration = Duration::from_millis(300);
+const TAKEOVER_TIMEOUT: Duration = Duration::from_millis(1000);
+
+pub struct Takeover {
+    pub epoch: u64,
+    pub started: Instant,
+    pub replies: HashSet<NodeId>,
+}
+
+#[derive(Default)]
+pub struct OrderedState {
+    // highest sequencer epoch seen; entries of older epochs are no longer accepted
+    pub epoch: u64,
+    // accepted entries, the newest epoch per slot
+    pub log: BTreeMap<u64, MlstOrdEntry>,
+    // entries a majority accepted: final, and the only ones ever delivered
+    pub committed: BTreeMap<u64, MlstOrdEntry>,
+    pub assigned: HashSet<MsgType>,
+    // every entry up to here is committed and delivered
+    pub delivered_upto: u64,
+    // highest committed slot heard of
+    pub high_seen: u64,
+    pub leading: bool,
+    pub next_seq: u64,
+    pub takeover: Option<Takeover>,
+    // while leading: who accepted each slot not committed yet
+    pub accepted_by: BTreeMap<u64, HashSet<NodeId>>,
+    // our own client values not delivered yet, with the time they were last submitted
+    pub submitted: HashMap<MsgType, Instant>,
+    pub last_sync: Option<Instant>,
+    pub last_nack: Option<Instant>,
+}
+
+// Total-order broadcast: the lowest node id not suspected to have failed acts as sequencer
+// and numbers every value. A numbered entry is only committed, and then delivered, once a
+// majority accepted it; nodes deliver committed entries strictly in order, asking for
+// retransmission of gaps. A new sequencer first collects the accepted log tails of a majority
+// under a higher epoch. Any committed entry is in at least one of those tails, so re-proposing
+// the newest entry seen per slot, and no-ops only where nobody had one, keeps every slot a
+// node may have delivered unchanged.
+pub trait MlstOrderedBroadcast: AsyncCommNode {
+    fn get_ordered_state(&self) -> &Mutex<OrderedState>;
+
+    // Overridden when a failure detector is around, to move the sequencer role on.
+    fn sequencer_suspected(&self, _peer: &NodeId) -> bool {
+        false
+    }
+
+    // A value resubmitted across a sequencer change can be committed twice; every node sees
+    // the same log, so keeping the first occurrence keeps the order the same everywhere.
+    fn ordered_messages(&self) -> Vec<MsgType> {
+        let state = self.get_ordered_state().lock().unwrap();
+        let mut seen = HashSet::new();
+        state
+            .committed
+            .range(..=state.delivered_upto)
+            .filter_map(|(_, entry)| entry.message)
+            .filter(|message| seen.insert(*message))
+            .collect()
+    }
+
+    fn ordered_sequencer(&self) -> Option<NodeId> {
+        let node_id = self.get_node_id().lock().unwrap().to_owned()?;
+        let mut node_ids = self.get_node_ids().lock().unwrap().to_owned();
+        node_ids.sort();
+        node_ids
+            .into_iter()
+            .find(|p| p == &node_id || !self.sequencer_suspected(p))
+    }
+
+    fn ordered_others(&self) -> Vec<NodeId> {
+        let node_id = self.get_node_id().lock().unwrap().to_owned();
+        self.get_node_ids()
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| Some(*p) != node_id.as_ref())
+            .cloned()
+            .collect()
+    }
+
+    fn ordered_majority(&self) -> usize {
+        self.get_node_ids().lock().unwrap().len() / 2 + 1
+    }
+
+    fn ordered_submit(&self, message: MsgType) {
+        self.get_ordered_state()
+            .lock()
+            .unwrap()
+            .submitted
+            .insert(message, Instant::now());
+        self.ordered_route_submit(message);
+    }
+
+    fn ordered_route_submit(&self, message: MsgType) {
+        let sequencer = match self.ordered_sequencer() {
+            Some(sequencer) => sequencer,
+            None => return,
+        };
+        if Some(&sequencer) == self.get_node_id().lock().unwrap().as_ref() {
+            self.ordered_assign(message);
+            return;
+        }
+        let submit = MlstBodyReqOrdSubmit {
+            msg_type: Self::get_route_ord_submit(),
+            message,
+        };
+        self.send(sequencer, submit);
+    }
+
+    // Values reaching a node that is not (yet) leading are dropped: submitters retry.
+    fn ordered_assign(&self, message: MsgType) {
+        let mut state = self.get_ordered_state().lock().unwrap();
+        if !state.leading || state.assigned.contains(&message) {
+            return;
+        }
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let entry = MlstOrdEntry {
+            seq,
+            epoch: state.epoch,
+            message: Some(message),
+        };
+        self.ordered_propose(&mut state, vec![entry]);
+    }
+
+    // Accept the entries ourselves and ask the others to accept them too.
+    fn ordered_propose(&self, state: &mut OrderedState, entries: Vec<MlstOrdEntry>) {
+        let Some(node_id) = self.get_node_id().lock().unwrap().to_owned() else {
+            return;
+        };
+        let mut seqs = Vec::new();
+        for entry in entries.iter() {
+            if self.ordered_accept(state, entry.to_owned()) {
+                state
+                    .accepted_by
+                    .insert(entry.seq, HashSet::from([node_id.to_owned()]));
+                seqs.push(entry.seq);
+            }
+        }
+        self.ordered_try_commit(state, &seqs);
+        let accept = MlstBodyReqOrdAccept {
+            msg_type: Self::get_route_ord_accept(),
+            entries,
+        };
+        for peer in self.ordered_others() {
+            self.send(peer, &accept);
+        }
+    }
+
+    // Returns whether the entry is (now) what we hold for its slot.
+    fn ordered_accept(&self, state: &mut OrderedState, entry: MlstOrdEntry) -> bool {
+        if state.committed.contains_key(&entry.seq) {
+            return false;
+        }
+        if let Some(existing) = state.log.get(&entry.seq) {
+            if existing.epoch >= entry.epoch {
+                return existing.epoch == entry.epoch;
+            }
+            if let Some(message) = existing.message {
+                state.assigned.remove(&message);
+            }
+        }
+        if let Some(message) = entry.message {
+            state.assigned.insert(message);
+        }
+        state.log.insert(entry.seq, entry);
+        true
+    }
+
+    fn ordered_try_commit(&self, state: &mut OrderedState, seqs: &[u64]) {
+        let majority = self.ordered_majority();
+        let mut entries = Vec::new();
+        for seq in seqs {
+            let accepted = state
+                .accepted_by
+                .get(seq)
+                .is_some_and(|accepted_by| accepted_by.len() >= majority);
+            if let (true, Some(entry)) = (accepted, state.log.get(seq).cloned()) {
+                self.ordered_commit(state, entry.to_owned());
+                entries.push(entry);
+            }
+        }
+        if entries.is_empty() {
+            return;
+        }
+        let commit = MlstBodyReqOrdCommit {
+            msg_type: Self::get_route_ord_commit(),
+            entries,
+        };
+        for peer in self.ordered_others() {
+            self.send(peer, &commit);
+        }
+    }
+
+    fn ordered_commit(&self, state: &mut OrderedState, entry: MlstOrdEntry) {
+        if entry.seq <= state.delivered_upto || state.committed.contains_key(&entry.seq) {
+            return;
+        }
+        state.accepted_by.remove(&entry.seq);
+        if let Some(replaced) = state.log.insert(entry.seq, entry.to_owned()) {
+            if let Some(message) = replaced.message {
+                state.assigned.remove(&message);
+            }
+        }
+        if let Some(message) = entry.message {
+            state.assigned.insert(message);
+        }
+        state.high_seen = state.high_seen.max(entry.seq);
+        state.committed.insert(entry.seq, entry);
+        while let Some(next) = state.committed.get(&(state.delivered_upto + 1)) {
+            if let Some(message) = next.message {
+                state.submitted.remove(&message);
+                self.store_message(message);
+            }
+            state.delivered_upto += 1;
+        }
+    }
+
+    fn ordered_observe_epoch(&self, state: &mut OrderedState, epoch: u64) {
+        if epoch <= state.epoch {
+            return;
+        }
+        if state.leading || state.takeover.is_some() {
+            self.log(&format!("ORDERED stepping down for epoch {}", epoch));
+        }
+        state.epoch = epoch;
+        state.leading = false;
+        state.takeover = None;
+        state.accepted_by.clear();
+    }
+
+    fn process_ord_submit(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqOrdSubmit = serde_json::from_value(body_req).unwrap();
+        self.ordered_assign(req_body.message);
+    }
+
+    // Entries of an epoch older than one we promised to are refused.
+    fn process_ord_accept(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqOrdAccept = serde_json::from_value(body_req).unwrap();
+        let (epoch, seqs) = {
+            let mut state = self.get_ordered_state().lock().unwrap();
+            let mut seqs = Vec::new();
+            for entry in req_body.entries {
+                self.ordered_observe_epoch(&mut state, entry.epoch);
+                if entry.epoch < state.epoch {
+                    continue;
+                }
+                let seq = entry.seq;
+                if self.ordered_accept(&mut state, entry) {
+                    seqs.push(seq);
+                }
+            }
+            (state.epoch, seqs)
+        };
+        if seqs.is_empty() {
+            return;
+        }
+        let resp_body = MlstBodyRespOrdAccept {
+            msg_type: Self::get_route_ord_accept_ok(),
+            epoch,
+            seqs,
+        };
+        self.send(src, resp_body);
+    }
+
+    fn process_ord_accept_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyRespOrdAccept = serde_json::from_value(body_req).unwrap();
+        let mut state = self.get_ordered_state().lock().unwrap();
+        if !state.leading || req_body.epoch != state.epoch {
+            return;
+        }
+        for seq in req_body.seqs.iter() {
+            if let Some(accepted_by) = state.accepted_by.get_mut(seq) {
+                accepted_by.insert(src.to_owned());
+            }
+        }
+        self.ordered_try_commit(&mut state, &req_body.seqs);
+    }
+
+    fn process_ord_commit(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqOrdCommit = serde_json::from_value(body_req).unwrap();
+        let mut state = self.get_ordered_state().lock().unwrap();
+        for entry in req_body.entries {
+            self.ordered_commit(&mut state, entry);
+        }
+    }
+
+    // Anyone holding the requested committed entries answers, not only the sequencer.
+    fn process_ord_nack(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqOrdNack = serde_json::from_value(body_req).unwrap();
+        let entries: Vec<MlstOrdEntry> = self
+            .get_ordered_state()
+            .lock()
+            .unwrap()
+            .committed
+            .range(req_body.from..=req_body.to)
+            .map(|(_, entry)| entry.to_owned())
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+        let commit = MlstBodyReqOrdCommit {
+            msg_type: Self::get_route_ord_commit(),
+            entries,
+        };
+        self.send(src, commit);
+    }
+
+    fn process_ord_sync(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqOrdSync = serde_json::from_value(body_req).unwrap();
+        let mut state = self.get_ordered_state().lock().unwrap();
+        self.ordered_observe_epoch(&mut state, req_body.epoch);
+        state.high_seen = state.high_seen.max(req_body.high);
+    }
+
+    // Replying promises to refuse entries of older epochs from now on.
+    fn process_ord_takeover(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqOrdTakeover = serde_json::from_value(body_req).unwrap();
+        let entries = {
+            let mut state = self.get_ordered_state().lock().unwrap();
+            if req_body.epoch <= state.epoch {
+                return;
+            }
+            self.ordered_observe_epoch(&mut state, req_body.epoch);
+            state
+                .log
+                .range(req_body.from..)
+                .map(|(_, entry)| entry.to_owned())
+                .collect()
+        };
+        let resp_body = MlstBodyRespOrdTakeover {
+            msg_type: Self::get_route_ord_takeover_ok(),
+            epoch: req_body.epoch,
+            entries,
+        };
+        self.send(src, resp_body);
+    }
+
+    fn process_ord_takeover_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyRespOrdTakeover = serde_json::from_value(body_req).unwrap();
+        let mut state = self.get_ordered_state().lock().unwrap();
+        match state.takeover.as_mut() {
+            Some(takeover) if takeover.epoch == req_body.epoch => {
+                takeover.replies.insert(src);
+            }
+            _ => return,
+        }
+        for entry in req_body.entries {
+            self.ordered_accept(&mut state, entry);
+        }
+    }
+
+    fn ordered_start_takeover(&self, state: &mut OrderedState, others: &[NodeId], now: Instant) {
+        let epoch = state.epoch + 1;
+        self.log(&format!("ORDERED taking over as sequencer, epoch {}", epoch));
+        // our own promise: nothing of an older epoch is accepted any more
+        state.epoch = epoch;
+        state.takeover = Some(Takeover {
+            epoch,
+            started: now,
+            replies: HashSet::new(),
+        });
+        let takeover = MlstBodyReqOrdTakeover {
+            msg_type: Self::get_route_ord_takeover(),
+            epoch,
+            from: state.delivered_upto + 1,
+        };
+        for peer in others.iter() {
+            self.send(peer.to_owned(), &takeover);
+        }
+    }
+
+    // With a majority of log tails in, re-propose the newest entry known for every slot not
+    // committed yet under our epoch, and a no-op where no one had any.
+    fn ordered_finish_takeover(&self, state: &mut OrderedState) {
+        state.takeover = None;
+        state.leading = true;
+        let epoch = state.epoch;
+        let high = state
+            .high_seen
+            .max(state.log.keys().last().copied().unwrap_or(0))
+            .max(state.delivered_upto);
+        let entries: Vec<MlstOrdEntry> = (state.delivered_upto + 1..=high)
+            .filter(|seq| !state.committed.contains_key(seq))
+            .map(|seq| MlstOrdEntry {
+                seq,
+                epoch,
+                message: state.log.get(&seq).and_then(|entry| entry.message),
+            })
+            .collect();
+        state.next_seq = high + 1;
+        self.ordered_propose(state, entries);
+    }
+
+    fn ordered_tick(&self) {
+        let node_id = match self.get_node_id().lock().unwrap().to_owned() {
+            Some(node_id) => node_id,
+            None => return,
+        };
+        let sequencer = match self.ordered_sequencer() {
+            Some(sequencer) => sequencer,
+            None => return,
+        };
+        let others = self.ordered_others();
+        let majority = self.ordered_majority();
+        let now = Instant::now();
+        let mut state = self.get_ordered_state().lock().unwrap();
+
+        if sequencer != node_id {
+            state.leading = false;
+            state.takeover = None;
+            state.accepted_by.clear();
+        } else if !state.leading {
+            // a takeover short of a majority never finishes; retry under a fresh epoch
+            let retry = state
+                .takeover
+                .as_ref()
+                .is_none_or(|takeover| now.duration_since(takeover.started) > TAKEOVER_TIMEOUT);
+            if retry {
+                self.ordered_start_takeover(&mut state, &others, now);
+            }
+        }
+
+        let takeover_done = state
+            .takeover
+            .as_ref()
+            .is_some_and(|takeover| takeover.replies.len() + 1 >= majority);
+        if takeover_done {
+            self.ordered_finish_takeover(&mut state);
+        }
+
+        if state.leading
+            && state
+                .last_sync
+                .is_none_or(|at| now.duration_since(at) >= SYNC_INTERVAL)
+        {
+            let sync = MlstBodyReqOrdSync {
+                msg_type: Self::get_route_ord_sync(),
+                epoch: state.epoch,
+                high: state.committed.keys().last().copied().unwrap_or(0),
+            };
+            for peer in others.iter() {
+                self.send(peer.to_owned(), &sync);
+            }
+            // accepts may have been lost too
+            for peer in others.iter() {
+                let entries: Vec<MlstOrdEntry> = state
+                    .accepted_by
+                    .iter()
+                    .filter(|(_, accepted_by)| !accepted_by.contains(peer))
+                    .filter_map(|(seq, _)| state.log.get(seq).cloned())
+                    .collect();
+                if entries.is_empty() {
+                    continue;
+                }
+                let accept = MlstBodyReqOrdAccept {
+                    msg_type: Self::get_route_ord_accept(),
+                    entries,
+                };
+                self.send(peer.to_owned(), accept);
+            }
+            state.last_sync = Some(now);
+        }
+
+        if sequencer != node_id
+            && state.high_seen > state.delivered_upto
+            && state
+                .last_nack
+                .is_none_or(|at| now.duration_since(at) >= NACK_INTERVAL)
+        {
+            let nack = MlstBodyReqOrdNack {
+                msg_type: Self::get_route_ord_nack(),
+                from: state.delivered_upto + 1,
+                to: state.high_seen,
+            };
+            self.send(sequencer, nack);
+            state.last_nack = Some(now);
+        }
+
+        let resubmit: Vec<MsgType> = state
+            .submitted
+            .iter()
+            .filter(|(_, at)| now.duration_since(**at) >= SUBMIT_TIMEOUT)
+            .map(|(message, _)| *message)
+            .collect();
+        for message in resubmit.iter() {
+            state.submitted.insert(*message, now);
+        }
+        drop(state);
+        for message in resubmit {
+            self.ordered_route_submit(message);
+        }
+    }
+
+    fn get_route_ord_submit() -> MsgTypeType;
+
+    fn get_route_ord_accept() -> MsgTypeType;
+
+    fn get_route_ord_accept_ok() -> MsgTypeType;
+
+    fn get_route_ord_commit() -> MsgTypeType;
+
+    fn get_route_ord_nack() -> MsgTypeType;
+
+    fn get_route_ord_sync() -> MsgTypeType;
+
+    fn get_route_ord_takeover() -> MsgTypeType;
+
+    fn get_route_ord_takeover_ok() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::node::{MsgType, MsgTypeType};
+    use serde::{Deserialize, Serialize};
+
+    // `message` is empty for slots a new sequencer filled with a no-op.
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct MlstOrdEntry {
+        pub seq: u64,
+        pub epoch: u64,
+        pub message: Option<MsgType>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqOrdSubmit {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub message: MsgType,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqOrdAccept {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub entries: Vec<MlstOrdEntry>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyRespOrdAccept {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub epoch: u64,
+        pub seqs: Vec<u64>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqOrdCommit {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub entries: Vec<MlstOrdEntry>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqOrdNack {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub from: u64,
+        pub to: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqOrdSync {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub epoch: u64,
+        pub high: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqOrdTakeover {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub epoch: u64,
+        pub from: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyRespOrdTakeover {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub epoch: u64,
+        pub entries: Vec<MlstOrdEntry>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BroadcastMode, Config};
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+
+    // The protocol talks to every node directly, so the submitter must notice a failed
+    // sequencer even when the two are not neighbors in the topology.
+    #[test]
+    fn failover_reaches_a_submitter_that_is_no_neighbor_of_the_sequencer() {
+        let mut cluster = Cluster::new(&["n1", "n2", "n3"], || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Ordered))
+        });
+        cluster.set_topology(&[("n1", &["n2"]), ("n2", &["n1", "n3"]), ("n3", &["n2"])]);
+        cluster.request("n3", json!({"type": "broadcast", "msg_id": 1, "message": 1}));
+        cluster.run_for(Duration::from_millis(1500));
+        assert_eq!(cluster.nodes["n3"].ordered_messages(), vec![1]);
+
+        cluster.down.insert("n1".to_string());
+        cluster.request("n3", json!({"type": "broadcast", "msg_id": 2, "message": 2}));
+        cluster.run_for(Duration::from_millis(3000));
+        for node_id in ["n2", "n3"] {
+            assert_eq!(cluster.nodes[node_id].ordered_messages(), vec![1, 2]);
+        }
+    }
+}
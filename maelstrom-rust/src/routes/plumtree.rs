@@ -0,0 +1,346 @@
+use crate::async_comm_node::{AsyncCommNode, MsgCachedKey};
+use crate::node::proto::MlstAckBodyReq;
+use crate::node::{CommId, MsgType, MsgTypeType, NodeId};
+use proto::{
+    MlstBodyReqGossip, MlstBodyReqGraft, MlstBodyReqIHave, MlstBodyReqPrune, MlstBodyRespGossip,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long to wait for the eager push after an IHAVE before grafting the announcer.
+const IHAVE_TIMEOUT: Duration = Duration::from_millis(500);
+// Senders are only needed while they may still retransmit, so just the latest ones are kept.
+const MAX_DELIVERED_FROM: usize = 4096;
+
+#[derive(Default)]
+pub struct PlumtreeState {
+    pub eager_peers: HashSet<NodeId>,
+    pub lazy_peers: HashSet<NodeId>,
+    // announcers of messages we only heard about, in arrival order
+    pub missing: HashMap<MsgType, Vec<NodeId>>,
+    pub graft_timers: HashMap<MsgType, Instant>,
+    // who delivered each message first, so retransmissions of it are not mistaken for duplicates
+    pub delivered_from: HashMap<MsgType, NodeId>,
+    pub delivered_order: VecDeque<MsgType>,
+}
+
+impl PlumtreeState {
+    // Keep eager/lazy peers in line with the current neighbor list: new neighbors start eager.
+    fn sync_peers(&mut self, neighbor_ids: &[NodeId]) {
+        self.eager_peers.retain(|p| neighbor_ids.contains(p));
+        self.lazy_peers.retain(|p| neighbor_ids.contains(p));
+        for neighbor_id in neighbor_ids {
+            if !self.lazy_peers.contains(neighbor_id) {
+                self.eager_peers.insert(neighbor_id.to_owned());
+            }
+        }
+    }
+
+    fn make_eager(&mut self, peer: &NodeId) {
+        self.lazy_peers.remove(peer);
+        self.eager_peers.insert(peer.to_owned());
+    }
+
+    fn make_lazy(&mut self, peer: &NodeId) {
+        self.eager_peers.remove(peer);
+        self.lazy_peers.insert(peer.to_owned());
+    }
+
+    fn note_delivered(&mut self, message: MsgType, src: &NodeId) {
+        if self.delivered_from.insert(message, src.to_owned()).is_none() {
+            self.delivered_order.push_back(message);
+        }
+        while self.delivered_order.len() > MAX_DELIVERED_FROM {
+            if let Some(oldest) = self.delivered_order.pop_front() {
+                self.delivered_from.remove(&oldest);
+            }
+        }
+    }
+}
+
+pub trait MlstPlumtree: AsyncCommNode {
+    fn get_plumtree_state(&self) -> &Mutex<PlumtreeState>;
+
+    fn plumtree_broadcast(&self, message: MsgType, round: u32, src: &NodeId) {
+        let neighbor_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
+        let (eager_peers, lazy_peers) = {
+            let mut state = self.get_plumtree_state().lock().unwrap();
+            state.sync_peers(&neighbor_ids);
+            state.missing.remove(&message);
+            state.graft_timers.remove(&message);
+            state.note_delivered(message, src);
+            (state.eager_peers.to_owned(), state.lazy_peers.to_owned())
+        };
+        for peer in eager_peers.iter().filter(|p| *p != src) {
+            self.eager_push(peer.to_owned(), message, round);
+        }
+        for peer in lazy_peers.iter().filter(|p| *p != src) {
+            let ihave = MlstBodyReqIHave {
+                msg_type: Self::get_route_ihave(),
+                message,
+                round,
+            };
+            self.send(peer.to_owned(), ihave);
+        }
+    }
+
+    fn eager_push(&self, dest: NodeId, message: MsgType, round: u32) {
+        let msg_id = self.next_msg_id();
+        let gossip = MlstBodyReqGossip {
+            msg_id,
+            msg_type: Self::get_route_gossip(),
+            message,
+            round,
+        };
+        self.await_communicate(msg_id, dest, gossip);
+    }
+
+    fn process_gossip(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        self.log("GOSSIP");
+        let req_body: MlstBodyReqGossip = serde_json::from_value(body_req).unwrap();
+        let resp_body = MlstBodyRespGossip {
+            msg_type: Self::get_route_gossip_ok(),
+        };
+        self.reply(req_body.msg_id, src.to_owned(), resp_body);
+        if !self.check_message(&req_body.message) {
+            self.store_message(req_body.message);
+            self.get_plumtree_state().lock().unwrap().make_eager(&src);
+            self.plumtree_broadcast(req_body.message, req_body.round + 1, &src);
+            return;
+        }
+        let mut state = self.get_plumtree_state().lock().unwrap();
+        if state.delivered_from.get(&req_body.message) == Some(&src) {
+            return;
+        }
+        state.make_lazy(&src);
+        drop(state);
+        let prune = MlstBodyReqPrune {
+            msg_type: Self::get_route_prune(),
+        };
+        self.send(src, prune);
+    }
+
+    fn process_gossip_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstAckBodyReq = serde_json::from_value(body_req).unwrap();
+        let key = MsgCachedKey {
+            msg_id: req_body.in_reply_to.to_owned(),
+            dest: src,
+        };
+        self.ack_delivered(&key);
+    }
+
+    fn process_ihave(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        self.log("IHAVE");
+        let req_body: MlstBodyReqIHave = serde_json::from_value(body_req).unwrap();
+        if self.check_message(&req_body.message) {
+            return;
+        }
+        let mut state = self.get_plumtree_state().lock().unwrap();
+        state
+            .missing
+            .entry(req_body.message)
+            .or_default()
+            .push(src);
+        state
+            .graft_timers
+            .entry(req_body.message)
+            .or_insert_with(|| Instant::now() + IHAVE_TIMEOUT);
+    }
+
+    fn process_graft(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        self.log("GRAFT");
+        let req_body: MlstBodyReqGraft = serde_json::from_value(body_req).unwrap();
+        self.get_plumtree_state().lock().unwrap().make_eager(&src);
+        if self.check_message(&req_body.message) {
+            self.eager_push(src, req_body.message, req_body.round);
+        }
+    }
+
+    fn process_prune(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        _body_req: serde_json::Value,
+    ) {
+        self.log("PRUNE");
+        self.get_plumtree_state().lock().unwrap().make_lazy(&src);
+    }
+
+    // Graft the tree back together for messages announced by IHAVE but never pushed to us.
+    fn plumtree_tick(&self) {
+        let now = Instant::now();
+        let mut grafts = Vec::new();
+        {
+            let mut state = self.get_plumtree_state().lock().unwrap();
+            let due: Vec<MsgType> = state
+                .graft_timers
+                .iter()
+                .filter(|(_, deadline)| **deadline <= now)
+                .map(|(message, _)| *message)
+                .collect();
+            for message in due {
+                let announcer = match state.missing.get_mut(&message) {
+                    Some(announcers) if !announcers.is_empty() => announcers.remove(0),
+                    _ => {
+                        state.missing.remove(&message);
+                        state.graft_timers.remove(&message);
+                        continue;
+                    }
+                };
+                state.make_eager(&announcer);
+                state.graft_timers.insert(message, now + IHAVE_TIMEOUT);
+                grafts.push((announcer, message));
+            }
+        }
+        for (announcer, message) in grafts {
+            if self.check_message(&message) {
+                continue;
+            }
+            let graft = MlstBodyReqGraft {
+                msg_type: Self::get_route_graft(),
+                message,
+                round: 0,
+            };
+            self.send(announcer, graft);
+        }
+    }
+
+    fn get_route_gossip() -> MsgTypeType;
+
+    fn get_route_gossip_ok() -> MsgTypeType;
+
+    fn get_route_ihave() -> MsgTypeType;
+
+    fn get_route_graft() -> MsgTypeType;
+
+    fn get_route_prune() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::node::{MsgId, MsgType, MsgTypeType};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqGossip {
+        pub msg_id: MsgId,
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub message: MsgType,
+        pub round: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct MlstBodyRespGossip {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqIHave {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub message: MsgType,
+        pub round: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqGraft {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub message: MsgType,
+        pub round: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqPrune {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{BroadcastMode, Config};
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+
+    fn plumtree_cluster(ids: &[&str]) -> Cluster<MlstService> {
+        Cluster::new(ids, || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Plumtree))
+        })
+    }
+
+    // A triangle carries every first message twice to someone; the second copy prunes the
+    // edge it came over, and later messages only announce themselves there.
+    #[test]
+    fn duplicates_prune_the_triangle_into_a_tree() {
+        let mut cluster = plumtree_cluster(&["n1", "n2", "n3"]);
+        cluster.set_topology(&[
+            ("n1", &["n2", "n3"]),
+            ("n2", &["n1", "n3"]),
+            ("n3", &["n1", "n2"]),
+        ]);
+        for value in 0..10 {
+            let broadcast = json!({"type": "broadcast", "msg_id": value, "message": value});
+            cluster.request("n1", broadcast);
+            cluster.settle();
+        }
+        let mut eager_links = 0;
+        for node in cluster.nodes.values() {
+            assert_eq!(node.messages.lock().unwrap().len(), 10);
+            let state = node.plumtree.lock().unwrap();
+            assert_eq!(state.eager_peers.len() + state.lazy_peers.len(), 2);
+            eager_links += state.eager_peers.len();
+        }
+        // two edges of the tree, seen from both of their ends
+        assert_eq!(eager_links, 4);
+    }
+
+    #[test]
+    fn missing_announced_message_is_grafted_once_the_timer_runs_out() {
+        let mut cluster = plumtree_cluster(&["n1", "n2"]);
+        cluster.set_topology(&[("n1", &["n2"]), ("n2", &["n1"])]);
+        cluster.nodes["n2"].store_message(7);
+        cluster.nodes["n2"].plumtree.lock().unwrap().make_lazy(&"n1".to_string());
+        cluster.nodes["n1"].plumtree.lock().unwrap().make_lazy(&"n2".to_string());
+        let ihave = json!({"type": "ihave", "message": 7, "round": 0});
+        cluster.nodes["n1"].process_ihave(None, "n2".to_string(), "n1".to_string(), ihave);
+        cluster.step();
+        assert!(!cluster.nodes["n1"].check_message(&7), "grafted before the timer ran out");
+        cluster.run_for(IHAVE_TIMEOUT * 2);
+        assert!(cluster.nodes["n1"].check_message(&7));
+        for (node_id, peer) in [("n1", "n2"), ("n2", "n1")] {
+            let state = cluster.nodes[node_id].plumtree.lock().unwrap();
+            assert!(state.eager_peers.contains(peer), "{} still lazy at {}", peer, node_id);
+            assert!(state.graft_timers.is_empty());
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::async_comm_node::AsyncCommNode;
+use crate::node::{CommId, MsgTypeType, NodeId};
+use crate::rng;
+use proto::{MlstBodyReqPushPull, MlstBodyRespPushPull};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub struct PushPullState {
+    pub last_round: Option<Instant>,
+}
+
+// Broadcast values are their own ids, so a digest of the message set is the set itself,
+// range encoded: each round the initiator pushes its digest and pulls back whatever it lacked.
+pub trait MlstPushPull: AsyncCommNode {
+    fn get_push_pull_state(&self) -> &Mutex<PushPullState>;
+
+    fn gossip_peers(&self) -> Vec<NodeId> {
+        self.all_peers()
+    }
+
+    fn all_peers(&self) -> Vec<NodeId> {
+        let node_id = self.get_node_id().lock().unwrap().to_owned();
+        self.get_node_ids()
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| Some(*p) != node_id.as_ref())
+            .cloned()
+            .collect()
+    }
+
+    fn push_pull_tick(&self, fanout: usize, interval: Duration) {
+        let now = Instant::now();
+        {
+            let mut state = self.get_push_pull_state().lock().unwrap();
+            if state
+                .last_round
+                .is_some_and(|at| now.duration_since(at) < interval)
+            {
+                return;
+            }
+            state.last_round = Some(now);
+        }
+        let peers = rng::sample(&self.gossip_peers(), fanout);
+        if peers.is_empty() {
+            return;
+        }
+        let digest = MlstBodyReqPushPull {
+            msg_type: Self::get_route_push_pull(),
+            messages: self.get_messages().lock().unwrap().to_owned(),
+        };
+        for peer in peers {
+            self.send(peer, &digest);
+        }
+    }
+
+    fn process_push_pull(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqPushPull = serde_json::from_value(body_req).unwrap();
+        let missing = self
+            .get_messages()
+            .lock()
+            .unwrap()
+            .difference(&req_body.messages);
+        self.store_messages(&req_body.messages);
+        let resp_body = MlstBodyRespPushPull {
+            msg_type: Self::get_route_push_pull_ok(),
+            messages: missing,
+        };
+        self.send(src, resp_body);
+    }
+
+    fn process_push_pull_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        _src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyRespPushPull = serde_json::from_value(body_req).unwrap();
+        self.store_messages(&req_body.messages);
+    }
+
+    fn get_route_push_pull() -> MsgTypeType;
+
+    fn get_route_push_pull_ok() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::interval_set::IntervalSet;
+    use crate::node::MsgTypeType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqPushPull {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub messages: IntervalSet,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyRespPushPull {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub messages: IntervalSet,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_comm_node::AsyncCommNode;
+    use crate::config::{BroadcastMode, Config};
+    use crate::interval_set::IntervalSet;
+    use crate::sim::{Cluster, SimNode};
+    use crate::MlstService;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    const IDS: [&str; 6] = ["n1", "n2", "n3", "n4", "n5", "n6"];
+
+    fn push_pull_cluster(fanout: usize) -> Cluster<MlstService> {
+        Cluster::new(&IDS, || {
+            let config = Config {
+                gossip_fanout: fanout,
+                gossip_interval: Duration::ZERO,
+                ..Config::with_broadcast(BroadcastMode::PushPull)
+            };
+            MlstService::with_config(config)
+        })
+    }
+
+    #[test]
+    fn each_round_pushes_to_fanout_distinct_peers() {
+        let cluster = push_pull_cluster(2);
+        let n1 = &cluster.nodes["n1"];
+        for _ in 0..20 {
+            n1.tick();
+            let dests: HashSet<String> = n1
+                .take_outbox()
+                .iter()
+                .map(|msg| {
+                    let msg: serde_json::Value = serde_json::from_str(msg).unwrap();
+                    assert_eq!(msg["body"]["type"], "push_pull");
+                    msg["dest"].as_str().unwrap().to_string()
+                })
+                .collect();
+            assert_eq!(dests.len(), 2);
+            assert!(!dests.contains("n1"));
+        }
+    }
+
+    #[test]
+    fn one_exchange_fills_the_gaps_of_both_sides() {
+        let mut cluster = push_pull_cluster(1);
+        cluster.nodes["n1"].store_messages(&(0..50).collect());
+        cluster.nodes["n2"].store_messages(&(40..80).chain([100]).collect());
+        // n2 is the only peer n1 can pick
+        let mut node_ids = cluster.nodes["n1"].node_ids.lock().unwrap();
+        node_ids.retain(|p| p == "n1" || p == "n2");
+        drop(node_ids);
+        cluster.nodes["n1"].tick();
+        cluster.flush();
+        cluster.flush();
+        let expected: IntervalSet = (0..80).chain([100]).collect();
+        for node_id in ["n1", "n2"] {
+            assert_eq!(*cluster.nodes[node_id].messages.lock().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn random_rounds_spread_every_value_everywhere() {
+        let mut cluster = push_pull_cluster(2);
+        for (value, node) in cluster.nodes.values().enumerate() {
+            node.store_message(value as i64 * 10);
+        }
+        for _ in 0..30 {
+            cluster.step();
+        }
+        let expected: IntervalSet = (0..IDS.len() as i64).map(|v| v * 10).collect();
+        for node in cluster.nodes.values() {
+            assert_eq!(*node.messages.lock().unwrap(), expected);
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use crate::node::{MsgId, MsgTypeType, Node, NodeId};
+use crate::async_comm_node::AsyncCommNode;
+use crate::node::{MsgId, MsgType, MsgTypeType, NodeId};
 use proto::{MlstBodyReqRead, MlstBodyRespRead};
 
-pub trait MlstRead: Node {
+pub trait MlstRead: AsyncCommNode {
     fn process_read(
         &self,
         _msg_id: Option<MsgId>,
@@ -11,35 +12,54 @@ pub trait MlstRead: Node {
     ) {
         self.log("READ");
         let req_body: MlstBodyReqRead = serde_json::from_value(req_body_raw).unwrap();
-        let resp_body = MlstBodyRespRead {
-            msg_type: "read_ok".to_string(),
-            messages: self
-                .get_messages()
-                .lock()
-                .unwrap()
-                .to_owned()
-                .into_iter()
-                .collect(),
+        // maelstrom's checker wants a flat array, other clients may ask for ranges instead,
+        // unless the order of the values matters, which ranges cannot carry
+        let resp_body = if req_body.ranges && !self.read_keeps_order() {
+            MlstBodyRespRead {
+                msg_type: "read_ok".to_string(),
+                messages: None,
+                ranges: Some(self.read_messages().into_iter().collect()),
+            }
+        } else {
+            MlstBodyRespRead {
+                msg_type: "read_ok".to_string(),
+                messages: Some(self.read_messages()),
+                ranges: None,
+            }
         };
         self.reply(req_body.msg_id, src, resp_body);
     }
 
+    fn read_messages(&self) -> Vec<MsgType> {
+        self.get_messages().lock().unwrap().iter().collect()
+    }
+
+    fn read_keeps_order(&self) -> bool {
+        false
+    }
+
     fn get_route_read() -> MsgTypeType;
 }
 
 pub mod proto {
+    use crate::interval_set::IntervalSet;
     use crate::node::{MsgId, MsgType, MsgTypeType};
     use serde::{Deserialize, Serialize};
 
     #[derive(Serialize, Deserialize)]
     pub struct MlstBodyReqRead {
         pub msg_id: MsgId,
+        #[serde(default)]
+        pub ranges: bool,
     }
 
     #[derive(Serialize, Deserialize, Clone)]
     pub struct MlstBodyRespRead {
         #[serde(rename = "type")]
         pub msg_type: MsgTypeType,
-        pub messages: Vec<MsgType>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub messages: Option<Vec<MsgType>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub ranges: Option<IntervalSet>,
     }
 }
@@ -1,20 +1,167 @@
-use crate::node::proto::MlstAckBodyReq;
-use crate::node::{CommId, MsgId, MsgTypeType, Node, NodeId};
+use crate::crdt::Crdt;
+use crate::crdt_node::CrdtNode;
+use crate::node::{CommId, MsgTypeType, NodeId};
+use proto::{MlstBodyReqReplicate, MlstBodyRespReplicate};
+
+// Delta-state replication: every round each peer gets the join of the delta groups it has
+// not acked yet, fire and forget. A lost message is simply covered by the next round, as
+// the peer's ack does not move. Peers that never acked, or lag behind the oldest group kept,
+// get the full state.
+pub trait MlstReplicate: CrdtNode {
+    fn replicate(&self) {
+        if self.get_node_id().lock().unwrap().is_none() {
+            return;
+        }
+        let peers = self.crdt_peers();
+        let mut outgoing = Vec::new();
+        let seq = {
+            let mut log = self.get_delta_log().lock().unwrap();
+            if log.seq == 0 {
+                return;
+            }
+            // also when no acks come in at all
+            log.prune(&peers);
+            let oldest = log.groups.keys().next().copied().unwrap_or(log.seq + 1);
+            for peer in peers {
+                let value = match log.acked.get(&peer).copied() {
+                    Some(acked) if acked >= log.seq => continue,
+                    Some(acked) if acked + 1 >= oldest => {
+                        let mut group = Self::Crdt::default();
+                        for (_, delta) in log.groups.range(acked + 1..) {
+                            group.merge(delta.to_owned());
+                        }
+                        group.state()
+                    }
+                    // the local state already holds every group up to `seq`
+                    _ => self.crdt_state(),
+                };
+                outgoing.push((peer, value));
+            }
+            log.seq
+        };
+        for (peer, value) in outgoing {
+            let replicate = MlstBodyReqReplicate {
+                msg_type: Self::get_route_replicate(),
+                value,
+                seq,
+            };
+            self.send(peer, replicate);
+        }
+    }
 
-pub trait MlstReplicate: Node {
     fn process_replicate(
         &self,
-        comm_id: Option<CommId>,
+        _comm_id: Option<CommId>,
         src: NodeId,
         _dest: NodeId,
         body_req: serde_json::Value,
     ) {
+        let req_body: MlstBodyReqReplicate<_> = serde_json::from_value(body_req).unwrap();
+        self.merge_state(req_body.value);
+        let resp_body = MlstBodyRespReplicate {
+            msg_type: Self::get_route_replicate_ok(),
+            seq: req_body.seq,
+        };
+        self.send(src, resp_body);
+    }
+
+    // Groups every peer has acked are no longer needed.
+    fn process_replicate_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyRespReplicate = serde_json::from_value(body_req).unwrap();
+        let peers = self.crdt_peers();
+        let mut log = self.get_delta_log().lock().unwrap();
+        let acked = log.acked.entry(src).or_default();
+        *acked = (*acked).max(req_body.seq);
+        log.prune(&peers);
     }
 
     fn get_route_replicate() -> MsgTypeType;
+
+    fn get_route_replicate_ok() -> MsgTypeType;
 }
 
 pub mod proto {
-    use crate::node::{MsgId, MsgType, MsgTypeType};
+    use crate::node::MsgTypeType;
     use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqReplicate<S> {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub value: S,
+        // everything up to this local delta sequence number is contained in `value`
+        pub seq: u64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyRespReplicate {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub seq: u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crdt::Crdt;
+    use crate::crdts::gcounter::GCounter;
+    use crate::crdts::gset::GSet;
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+
+    fn add(cluster: &mut Cluster<MlstService<GCounter>>, dest: &str, delta: u64) {
+        cluster.request(dest, json!({"type": "add", "msg_id": 1, "delta": delta}));
+    }
+
+    #[test]
+    fn adds_on_every_node_reach_every_read() {
+        let mut cluster = Cluster::new(&["n1", "n2", "n3"], MlstService::<GSet>::new);
+        for (element, dest) in ["n1", "n2", "n3"].into_iter().cycle().take(30).enumerate() {
+            cluster.request(dest, json!({"type": "add", "msg_id": 1, "element": element}));
+            if element % 4 == 0 {
+                cluster.step();
+            }
+        }
+        cluster.settle();
+        cluster.replies.clear();
+        for dest in ["n1", "n2", "n3"] {
+            cluster.request(dest, json!({"type": "read", "msg_id": 2}));
+        }
+        cluster.flush();
+        for (_, body) in cluster.replies.iter() {
+            assert_eq!(body["type"], "read_ok");
+            assert_eq!(body["value"], json!((0..30).collect::<Vec<_>>()));
+        }
+        assert_eq!(cluster.replies.len(), 3);
+        for node in cluster.nodes.values() {
+            let log = node.delta_log.lock().unwrap();
+            assert!(log.groups.is_empty(), "acked groups are dropped");
+        }
+    }
+
+    #[test]
+    fn a_dead_peer_does_not_grow_the_log_and_catches_up_from_full_state() {
+        // no acks come in at all while n2 is down
+        let mut cluster = Cluster::new(&["n1", "n2"], MlstService::<GCounter>::new);
+        cluster.down.insert("n2".to_string());
+        for _ in 0..1500 {
+            add(&mut cluster, "n1", 1);
+            cluster.step();
+        }
+        let groups = cluster.nodes["n1"].delta_log.lock().unwrap().groups.len();
+        assert!(groups <= 1024, "{} groups kept", groups);
+        cluster.down.clear();
+        cluster.settle();
+        for node in cluster.nodes.values() {
+            assert_eq!(node.crdt.lock().unwrap().value(), 1500);
+        }
+        assert!(cluster.nodes["n1"].delta_log.lock().unwrap().groups.is_empty());
+    }
 }
@@ -0,0 +1,248 @@
+use crate::async_comm_node::{AsyncCommNode, MsgCached, MsgCachedKey};
+use crate::interval_set::IntervalSet;
+use crate::node::proto::MlstAckBodyReq;
+use crate::node::{CommId, MsgTypeType, NodeId};
+use proto::{MlstBodyReqSync, MlstBodyRespSync};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// A retransmitted message acked after this long without any ack means the link was down.
+const HEAL_SILENCE: Duration = Duration::from_millis(1000);
+
+#[derive(Default)]
+pub struct HealState {
+    pub last_ack: HashMap<NodeId, Instant>,
+    // both the failure detector and the ack path may notice the same heal
+    pub last_heal: HashMap<NodeId, Instant>,
+}
+
+// Keeps the store consistent across topology changes: peers that left stop being retried,
+// peers that joined get the whole backlog in one reliable `sync` instead of one broadcast
+// per message. Peers coming back from a partition get a two way sync of the same kind.
+pub trait MlstNeighborSync: AsyncCommNode {
+    fn get_heal_state(&self) -> &Mutex<HealState>;
+
+    fn replace_neighbors(&self, new_ids: Vec<NodeId>) {
+        let old_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
+        self.set_neighbor_ids(new_ids.to_owned());
+        self.reconcile_neighbors(&old_ids, &new_ids);
+    }
+
+    fn reconcile_neighbors(&self, old_ids: &[NodeId], new_ids: &[NodeId]) {
+        for removed in old_ids.iter().filter(|p| !new_ids.contains(p)) {
+            self.forget_peer(removed);
+        }
+        let messages = self.get_messages().lock().unwrap().to_owned();
+        if messages.is_empty() {
+            return;
+        }
+        for added in new_ids.iter().filter(|p| !old_ids.contains(p)) {
+            self.log(&format!("SYNC {} messages to new neighbor {}", messages.len(), added));
+            self.sync_to(added, &messages);
+        }
+    }
+
+    fn sync_to(&self, peer: &NodeId, messages: &IntervalSet) {
+        self.send_sync(peer, messages, false);
+    }
+
+    // With `pull` set the peer answers with a sync of whatever we are missing.
+    fn send_sync(&self, peer: &NodeId, messages: &IntervalSet, pull: bool) {
+        let msg_id = self.next_msg_id();
+        let sync = MlstBodyReqSync {
+            msg_id,
+            msg_type: Self::get_route_sync(),
+            messages: messages.to_owned(),
+            pull,
+        };
+        self.await_communicate(msg_id, peer.to_owned(), sync);
+    }
+
+    // The first ack of a retransmitted message after a long silence is a heal we would
+    // otherwise only notice through the resend loop slowly draining.
+    fn note_ack(&self, peer: &NodeId, msg_cached: &MsgCached) {
+        let now = Instant::now();
+        let silent = {
+            let mut state = self.get_heal_state().lock().unwrap();
+            let last_ack = state.last_ack.insert(peer.to_owned(), now);
+            msg_cached.retries > 0
+                && last_ack.is_some_and(|at| now.duration_since(at) >= HEAL_SILENCE)
+        };
+        if silent {
+            self.heal_with(peer);
+        }
+    }
+
+    // Exchange full message sets with a peer that just became reachable again.
+    fn heal_with(&self, peer: &NodeId) {
+        let now = Instant::now();
+        {
+            let mut state = self.get_heal_state().lock().unwrap();
+            let recent = state
+                .last_heal
+                .get(peer)
+                .is_some_and(|at| now.duration_since(*at) < HEAL_SILENCE);
+            if recent {
+                return;
+            }
+            state.last_heal.insert(peer.to_owned(), now);
+        }
+        let messages = self.get_messages().lock().unwrap().to_owned();
+        self.log(&format!("HEAL {} resyncing {} messages", peer, messages.len()));
+        self.send_sync(peer, &messages, true);
+    }
+
+    // Store what is new and pass only that on, so the backlog spreads past the new link.
+    fn process_sync(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqSync = serde_json::from_value(body_req).unwrap();
+        let resp_body = MlstBodyRespSync {
+            msg_type: Self::get_route_sync_ok(),
+        };
+        self.reply(req_body.msg_id, src.to_owned(), resp_body);
+        if req_body.pull {
+            let missing = self
+                .get_messages()
+                .lock()
+                .unwrap()
+                .difference(&req_body.messages);
+            if !missing.is_empty() {
+                self.sync_to(&src, &missing);
+            }
+        }
+        let fresh = req_body
+            .messages
+            .difference(&self.get_messages().lock().unwrap());
+        if fresh.is_empty() {
+            return;
+        }
+        self.store_messages(&fresh);
+        let neighbor_ids = self.get_neighbor_ids().lock().unwrap().to_owned();
+        for neighbor_id in neighbor_ids.iter().filter(|p| **p != src) {
+            self.sync_to(neighbor_id, &fresh);
+        }
+    }
+
+    fn process_sync_ok(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstAckBodyReq = serde_json::from_value(body_req).unwrap();
+        let key = MsgCachedKey {
+            msg_id: req_body.in_reply_to.to_owned(),
+            dest: src,
+        };
+        self.ack_delivered(&key);
+    }
+
+    fn get_route_sync() -> MsgTypeType;
+
+    fn get_route_sync_ok() -> MsgTypeType;
+}
+
+pub mod proto {
+    use crate::interval_set::IntervalSet;
+    use crate::node::{MsgId, MsgTypeType};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqSync {
+        pub msg_id: MsgId,
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+        pub messages: IntervalSet,
+        #[serde(default)]
+        pub pull: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct MlstBodyRespSync {
+        #[serde(rename = "type")]
+        pub msg_type: MsgTypeType,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HEAL_SILENCE;
+    use crate::async_comm_node::AsyncCommNode;
+    use crate::config::{BroadcastMode, Config};
+    use crate::interval_set::IntervalSet;
+    use crate::sim::Cluster;
+    use crate::MlstService;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn topology_change_drops_a_removed_neighbor_and_syncs_an_added_one() {
+        let mut cluster = Cluster::new(&["n1", "n2", "n3", "n4"], || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Flood))
+        });
+        cluster.set_topology(&[
+            ("n1", &["n2", "n3"]),
+            ("n2", &["n1"]),
+            ("n3", &["n1"]),
+            ("n4", &[]),
+        ]);
+        cluster.down.insert("n3".to_string());
+        for value in 0..100 {
+            let broadcast = json!({"type": "broadcast", "msg_id": value, "message": value});
+            cluster.request("n1", broadcast);
+            cluster.step();
+        }
+        let to_n3 = |cluster: &Cluster<MlstService>| {
+            let n1 = &cluster.nodes["n1"];
+            let pending = n1.pending_ack_ids.lock().unwrap();
+            let queued = n1.peer_windows.lock().unwrap().get("n3").map_or(0, |w| w.queue.len());
+            pending.keys().filter(|key| key.dest == "n3").count() + queued
+        };
+        assert_eq!(to_n3(&cluster), 100);
+
+        cluster.set_topology(&[
+            ("n1", &["n2", "n4"]),
+            ("n2", &["n1"]),
+            ("n3", &[]),
+            ("n4", &["n1"]),
+        ]);
+        assert_eq!(to_n3(&cluster), 0);
+        cluster.settle();
+        assert_eq!(cluster.nodes["n4"].messages.lock().unwrap().len(), 100);
+        assert!(cluster.nodes["n1"].pending_ack_ids.lock().unwrap().is_empty());
+    }
+
+    // The values n2 took in while cut off never travel over the resend loop; the ack that
+    // ends the silence has to bring them over.
+    #[test]
+    fn ack_after_a_partition_pulls_what_the_peer_stored_meanwhile() {
+        let mut cluster = Cluster::new(&["n1", "n2"], || {
+            MlstService::with_config(Config::with_broadcast(BroadcastMode::Flood))
+        });
+        cluster.set_topology(&[("n1", &["n2"]), ("n2", &["n1"])]);
+        cluster.request("n1", json!({"type": "broadcast", "msg_id": 1, "message": 1}));
+        cluster.settle();
+
+        cluster.down.insert("n2".to_string());
+        cluster.request("n1", json!({"type": "broadcast", "msg_id": 2, "message": 2}));
+        cluster.nodes["n2"].store_messages(&(100..110).collect());
+        cluster.run_for(HEAL_SILENCE + Duration::from_millis(300));
+        cluster.down.clear();
+        // settle alone would stop before the next retransmission is due
+        cluster.run_for(Duration::from_millis(500));
+        cluster.settle();
+
+        let expected: IntervalSet = [1, 2].into_iter().chain(100..110).collect();
+        for node in cluster.nodes.values() {
+            assert_eq!(*node.messages.lock().unwrap(), expected);
+        }
+        assert!(cluster.nodes["n1"].heal.lock().unwrap().last_heal.contains_key("n2"));
+    }
+}
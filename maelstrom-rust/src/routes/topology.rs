@@ -11,9 +11,10 @@ pub trait MlstTopology: Node {
     ) {
         self.log("TOPOLOGY");
         let req_body: MlstBodyReqTopology = serde_json::from_value(body_req).unwrap();
-        let node_id_lock = self.get_node_id().lock();
-        let topology = req_body.topology[node_id_lock.unwrap().as_ref().unwrap()].to_owned();
-        self.set_neighbor_ids(topology);
+        let node_id = self.get_node_id().lock().unwrap().to_owned().unwrap();
+        // the node id lock is released first, applying may log and send
+        let topology = req_body.topology[&node_id].to_owned();
+        self.apply_topology(topology);
         let resp_body = MlstBodyRespTopology {
             msg_type: "topology_ok".to_string(),
         };
@@ -21,6 +22,10 @@ pub trait MlstTopology: Node {
     }
 
     fn get_route_topology() -> MsgTypeType;
+
+    fn apply_topology(&self, neighbor_ids: Vec<NodeId>) {
+        self.set_neighbor_ids(neighbor_ids);
+    }
 }
 
 pub mod proto {
@@ -0,0 +1,32 @@
+use crate::crdt::CrdtWorkload;
+use crate::crdt_node::CrdtNode;
+use crate::node::{CommId, NodeId};
+use proto::MlstBodyReqWorkload;
+
+// One route for all client requests of the workload; the CRDT decides what they mean.
+pub trait MlstWorkload: CrdtNode<Crdt: CrdtWorkload> {
+    fn process_workload(
+        &self,
+        _comm_id: Option<CommId>,
+        src: NodeId,
+        _dest: NodeId,
+        body_req: serde_json::Value,
+    ) {
+        let req_body: MlstBodyReqWorkload<_> = serde_json::from_value(body_req).unwrap();
+        let node_id = self.get_node_id().lock().unwrap().to_owned().unwrap();
+        let resp_body = self.mutate(|crdt| crdt.handle(&node_id, req_body.request));
+        self.reply(req_body.msg_id, src, resp_body);
+    }
+}
+
+pub mod proto {
+    use crate::node::MsgId;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MlstBodyReqWorkload<R> {
+        pub msg_id: MsgId,
+        #[serde(flatten)]
+        pub request: R,
+    }
+}
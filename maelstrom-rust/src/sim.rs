@@ -0,0 +1,129 @@
+use crate::node::{Node, NodeId};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+// Rounds `settle` runs at most, so a protocol that never goes quiet fails instead of hanging.
+const MAX_ROUNDS: usize = 1000;
+// Wall-clock time between the rounds of `run_for`.
+const ROUND_PAUSE: Duration = Duration::from_millis(10);
+
+// A node that can run on the in-process network: it writes into an outbox instead of stdout
+// and does its periodic work when ticked.
+pub trait SimNode: Node {
+    fn take_outbox(&self) -> Vec<String>;
+
+    fn tick(&self);
+}
+
+// In-process network for tests. Every round ticks the nodes and hands whatever they sent to
+// the destination, the way maelstrom would. Messages to ids that are not nodes are client
+// replies and are kept in `replies`.
+pub struct Cluster<N> {
+    pub nodes: BTreeMap<NodeId, N>,
+    pub replies: Vec<(NodeId, serde_json::Value)>,
+    // crashed nodes neither tick nor receive
+    pub down: HashSet<NodeId>,
+    next_id: i64,
+}
+
+impl<N: SimNode> Cluster<N> {
+    pub fn new(ids: &[&str], make: impl Fn() -> N) -> Self {
+        let node_ids: Vec<NodeId> = ids.iter().map(|id| id.to_string()).collect();
+        let mut cluster = Self {
+            nodes: node_ids.iter().map(|id| (id.to_owned(), make())).collect(),
+            replies: Vec::new(),
+            down: HashSet::new(),
+            next_id: 0,
+        };
+        for node_id in node_ids.iter() {
+            let init =
+                json!({"type": "init", "msg_id": 0, "node_id": node_id, "node_ids": node_ids});
+            cluster.request(node_id, init);
+        }
+        for node in cluster.nodes.values() {
+            node.take_outbox();
+        }
+        cluster
+    }
+
+    // Every node gets its own entry of `topology`.
+    pub fn set_topology(&mut self, topology: &[(&str, &[&str])]) {
+        let topology: BTreeMap<&str, &[&str]> = topology.iter().copied().collect();
+        for node_id in topology.keys() {
+            let request = json!({"type": "topology", "msg_id": 0, "topology": topology});
+            self.request(node_id, request);
+        }
+    }
+
+    // Sends `body` to `dest` as a client would.
+    pub fn request(&mut self, dest: &str, body: serde_json::Value) {
+        self.deliver("c1".to_string(), dest.to_string(), body);
+    }
+
+    fn deliver(&mut self, src: NodeId, dest: NodeId, body: serde_json::Value) {
+        self.next_id += 1;
+        let Some(node) = self.nodes.get(&dest) else {
+            self.replies.push((dest, body));
+            return;
+        };
+        if self.down.contains(&dest) {
+            return;
+        }
+        let msg_type = body["type"].as_str().unwrap().to_string();
+        node.observe_received(&src);
+        node.dispatch_request(Some(self.next_id), msg_type, src, dest, body);
+    }
+
+    // One round: tick every live node, then deliver what was sent. Returns how many
+    // messages went out.
+    pub fn step(&mut self) -> usize {
+        for (node_id, node) in self.nodes.iter() {
+            if !self.down.contains(node_id) {
+                node.tick();
+            }
+        }
+        self.flush()
+    }
+
+    // Delivers what the nodes sent so far, without ticking them.
+    pub fn flush(&mut self) -> usize {
+        let mut sent = Vec::new();
+        for (node_id, node) in self.nodes.iter() {
+            let outbox = node.take_outbox();
+            if self.down.contains(node_id) {
+                continue;
+            }
+            for msg in outbox {
+                let msg: serde_json::Value = serde_json::from_str(&msg).unwrap();
+                sent.push(msg);
+            }
+        }
+        let count = sent.len();
+        for msg in sent {
+            let src = msg["src"].as_str().unwrap().to_string();
+            let dest = msg["dest"].as_str().unwrap().to_string();
+            self.deliver(src, dest, msg["body"].to_owned());
+        }
+        count
+    }
+
+    // Runs rounds for `duration` of wall-clock time, for protocols driven by timers.
+    pub fn run_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while Instant::now() < until {
+            self.step();
+            std::thread::sleep(ROUND_PAUSE);
+        }
+    }
+
+    // Runs rounds until one sends nothing.
+    pub fn settle(&mut self) {
+        for _ in 0..MAX_ROUNDS {
+            if self.step() == 0 {
+                return;
+            }
+        }
+        panic!("network did not settle in {} rounds", MAX_ROUNDS);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::node::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Missing entries count as zero.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(transparent)]
+pub struct VectorClock(pub HashMap<NodeId, u64>);
+
+impl VectorClock {
+    pub fn get(&self, node_id: &NodeId) -> u64 {
+        self.0.get(node_id).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, node_id: &NodeId, value: u64) {
+        self.0.insert(node_id.to_owned(), value);
+    }
+
+    pub fn increment(&mut self, node_id: &NodeId) -> u64 {
+        let value = self.get(node_id) + 1;
+        self.set(node_id, value);
+        value
+    }
+}
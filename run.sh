@@ -20,8 +20,9 @@ function async-comm-service() {
 
 function crdt-service() {
 	workload="$1"
-	node="$SRC_DIR/target/debug/async-comm-service"
+	node="$SRC_DIR/target/debug/crdt-service"
 	shift
+	export MLST_CRDT="${MLST_CRDT:-$workload}"
 	pushd "$SRC_DIR"
 	cargo build
 	popd